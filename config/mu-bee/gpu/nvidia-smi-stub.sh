@@ -0,0 +1,15 @@
+#!/bin/sh
+# Stands in for nvidia-smi on machines without a GPU, by replaying recorded output.
+#
+#     gpu-query: /path/to/nvidia-smi-stub.sh --query-gpu=index,uuid,name,utilization.gpu,memory.used,memory.total --format=csv
+#     gpu-process-query: /path/to/nvidia-smi-stub.sh --query-compute-apps=gpu_uuid,pid,used_memory --format=csv
+#
+# The pids in the recorded output will not match any process on this machine,
+# so their names and users show up as '?'.
+
+dir=$(dirname "$0")
+case "$*" in
+	*--query-gpu=*) cat "$dir/nvidia-smi-gpus.csv" ;;
+	*--query-compute-apps=*) cat "$dir/nvidia-smi-apps.csv" ;;
+	*) echo "nvidia-smi-stub: unsupported arguments: $*" >&2; exit 2 ;;
+esac
@@ -9,6 +9,11 @@ bee=$base/mu-bee
 beelog=$base/beelog
 log=$base/hive.log
 
+# Don't touch the log file while a previous run is still writing to it. mu-hive takes its own
+# lock on $output.lock as well, so this one must be a different file.
+exec 9> $base/ex.lock
+flock -n 9 || exit 0
+
 # If it exists, back up the old log file.
 [ ! -f $log ] || mv $log $log.old
-$base/mu-hive --machines $machines --output $output --bee $bee --bee-log $beelog 2> $log
+$base/mu-hive --machines $machines --output $output --bee $bee --bee-log $beelog --keep 3 2> $log
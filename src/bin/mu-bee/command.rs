@@ -0,0 +1,69 @@
+//! Running the external commands that the bee asks for information, such as `nvidia-smi` and
+//! `loginctl`.
+//!
+//! These commands are not under our control and may hang, for instance on a GPU driver that is
+//! stuck. Each one is therefore given a timeout, after which it is killed and its output dropped.
+
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+/// Runs `command` through the shell and returns its output, giving up after `timeout`.
+pub fn run_query(command: &str, timeout: Duration) -> std::io::Result<String> {
+    let mut child = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+
+    // Read the output on the side, such that a chatty command cannot fill up the pipe and stall.
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reader = std::thread::spawn(move || {
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).map(|_| output)
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(std::io::Error::other(format!(
+                "{command:?} did not finish within {timeout:?}"
+            )));
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    if !status.success() {
+        return Err(std::io::Error::other(format!("{command:?} exited with {status}")));
+    }
+    reader.join().expect("reader thread does not panic")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn returns_the_output() {
+        let output = run_query("echo one; echo two >&2; echo three", Duration::from_secs(5));
+        assert_eq!(output.unwrap(), "one\nthree\n");
+    }
+
+    #[test]
+    fn failing_commands_give_an_error() {
+        assert!(run_query("echo partial; exit 1", Duration::from_secs(5)).is_err());
+        assert!(run_query("/nonexistent/command", Duration::from_secs(5)).is_err());
+    }
+
+    #[test]
+    fn slow_commands_time_out() {
+        let start = Instant::now();
+        assert!(run_query("sleep 10", Duration::from_millis(100)).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -1,36 +1,204 @@
-use std::collections::HashMap;
+//! The configuration of the bee, typically called `ignore.linus`.
+//!
+//! # Rules
+//!
+//! The names in the `ignore-user`, `ignore-proc`, and `rename-proc` rules are patterns.
+//!
+//! - A name between slashes, such as `/^python3\.\d+$/`, is a regular expression. It matches if
+//!   it matches any part of a name, so anchor it to match whole names.
+//! - A name containing `*`, `?`, or `[`, such as `tracker-*`, is a glob. It matches whole names.
+//! - Any other name matches exactly.
+//!
+//! `ignore-uid` rules take a user id, such as `65534`, or an inclusive range, such as `0-999`. Either
+//! end of a range may be left open, as in `60000-`.
+//!
+//! The rules are evaluated in a fixed order. First, a process is ignored if any `ignore-user` or
+//! `ignore-uid` rule matches its user, or if any `ignore-proc` rule matches its name. Otherwise,
+//! the `rename-proc` rules are tried in the order in which they appear in the file, and the first
+//! one that matches renames the process. Process names are matched in their full form first, and
+//! in their truncated comm form second (see [`crate::model::process_names`]).
+//!
+//! The replacement of a regular expression rename may refer to its capture groups, as in
+//! `rename-proc: /^(\w+)-worker$/ -> $1`. The whole name is replaced by the expanded replacement.
+//!
+//! # Thresholds
+//!
+//! A process is reported if it uses at least `cpu-threshold` percent of a core, at least
+//! `mem-threshold` of resident memory, such as `4G`, or at least `io-threshold` of disk throughput,
+//! such as `50M/s`. With `max-processes`, only that many of them are reported, the ones using the
+//! most CPU first. See [`Thresholds::default`] for the defaults.
+
+use std::ops::RangeInclusive;
 use std::str::FromStr;
 
+use mu::model::Thresholds;
+
+/// A name, glob, or regular expression that names are matched against.
+#[derive(Debug, Clone)]
+enum Pattern {
+    Exact(String),
+    Regex(regex::Regex),
+}
+
+impl Pattern {
+    fn parse(s: &str) -> Result<Self, regex::Error> {
+        if let Some(regex) = s.strip_prefix('/').and_then(|s| s.strip_suffix('/')) {
+            return regex::Regex::new(regex).map(Self::Regex);
+        }
+        if s.contains(['*', '?', '[']) {
+            return regex::Regex::new(&glob_to_regex(s)).map(Self::Regex);
+        }
+        Ok(Self::Exact(s.to_string()))
+    }
+
+    fn is_match(&self, name: &str) -> bool {
+        match self {
+            Pattern::Exact(exact) => exact == name,
+            Pattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+
+    /// Returns `replacement` for a matching `name`, with its capture group references expanded.
+    fn rename(&self, name: &str, replacement: &str) -> Option<String> {
+        match self {
+            Pattern::Exact(exact) => (exact == name).then(|| replacement.to_string()),
+            Pattern::Regex(regex) => {
+                let captures = regex.captures(name)?;
+                let mut renamed = String::new();
+                captures.expand(replacement, &mut renamed);
+                Some(renamed)
+            }
+        }
+    }
+}
+
+/// Translates a glob into an anchored regular expression.
+///
+/// A `*` matches any number of characters, a `?` matches a single character, and a bracketed
+/// class such as `[0-9]` or `[!a]` matches a single character from (or not from) the class.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                regex.push('[');
+                if chars.next_if_eq(&'!').is_some() {
+                    regex.push('^');
+                }
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    if c == '\\' || c == '[' {
+                        regex.push('\\');
+                    }
+                    regex.push(c);
+                }
+                regex.push(']');
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
 #[derive(Debug, Default)]
 struct Ignore {
-    processes: Box<[String]>,
-    users: Box<[String]>,
+    processes: Box<[Pattern]>,
+    users: Box<[Pattern]>,
+    uids: Box<[RangeInclusive<u32>]>,
 }
 
 #[derive(Debug, Default)]
 struct Rename {
-    dictionary: HashMap<String, String>,
+    /// The `(pattern, replacement)` rules, in the order in which they appear in the config.
+    rules: Box<[(Pattern, String)]>,
+}
+
+/// Selection of the mounted file systems to report.
+///
+/// A mount is reported if its mount point or its file system type is listed. If neither is
+/// configured, the mounts with a file system type from [`DEFAULT_MOUNT_TYPES`] are reported.
+#[derive(Debug, Default)]
+struct Mounts {
+    points: Box<[String]>,
+    types: Box<[String]>,
+    /// Seconds after which a responding mount is considered slow.
+    slow: Option<f32>,
+    /// Seconds after which a mount that has not responded is considered hung.
+    timeout: Option<f32>,
+}
+
+/// Seconds after which a responding mount is considered slow, unless configured otherwise.
+const DEFAULT_MOUNT_SLOW: f32 = 0.5;
+/// Seconds after which a mount that has not responded is considered hung, unless configured
+/// otherwise.
+const DEFAULT_MOUNT_TIMEOUT: f32 = 2.0;
+
+/// File system types that are reported if the config does not select any mounts.
+pub const DEFAULT_MOUNT_TYPES: &[&str] =
+    &["ext2", "ext3", "ext4", "xfs", "btrfs", "zfs", "f2fs", "nfs", "nfs4", "cifs", "smb3"];
+
+/// Commands for querying the GPUs. See [`crate::gpu`] for the output they must produce.
+#[derive(Debug, Default)]
+pub struct GpuQueries {
+    pub gpus: Option<String>,
+    pub processes: Option<String>,
 }
 
 #[derive(Debug, Default)]
 pub struct Config {
     ignore: Ignore,
     rename: Rename,
+    mounts: Mounts,
+    pub gpu_queries: GpuQueries,
+    pub thresholds: Thresholds,
 }
 
 impl Config {
-    pub fn is_ignored_user(&self, user: &str) -> bool {
-        // TODO: This silly allocation seems so dumb to me. Maybe it gets factored out?
-        self.ignore.users.contains(&user.to_string())
+    /// Returns whether a user is ignored, given their name and, if known, their user id.
+    pub fn is_ignored_user(&self, user: &str, uid: Option<u32>) -> bool {
+        let Ignore { users, uids, .. } = &self.ignore;
+        users.iter().any(|pattern| pattern.is_match(user))
+            || uid.is_some_and(|uid| uids.iter().any(|range| range.contains(&uid)))
+    }
+
+    /// Returns whether a process is ignored, given its full name and its comm name.
+    ///
+    /// A rule may name either form, such that `gsd-housekeeping` and `gsd-housekeepin` both match.
+    /// See [`crate::model::process_names`].
+    pub fn is_ignored_process(&self, name: &str, comm: &str) -> bool {
+        let processes = &self.ignore.processes;
+        processes.iter().any(|pattern| pattern.is_match(name) || pattern.is_match(comm))
+    }
+
+    /// Returns the name a process is renamed to, given its full name and its comm name.
+    ///
+    /// The first rule that matches either name applies. A rule is matched against the full name
+    /// before the comm name.
+    pub fn get_canonical_name(&self, name: &str, comm: &str) -> Option<String> {
+        self.rename.rules.iter().find_map(|(pattern, replacement)| {
+            pattern.rename(name, replacement).or_else(|| pattern.rename(comm, replacement))
+        })
     }
 
-    pub fn is_ignored_process(&self, proc: &str) -> bool {
-        // TODO: This silly allocation seems so dumb to me. Maybe it gets factored out?
-        self.ignore.processes.contains(&proc.to_string())
+    /// Returns the durations after which a mount probe is considered slow and hung, respectively.
+    pub fn mount_probe_limits(&self) -> (std::time::Duration, std::time::Duration) {
+        let slow = self.mounts.slow.unwrap_or(DEFAULT_MOUNT_SLOW);
+        let timeout = self.mounts.timeout.unwrap_or(DEFAULT_MOUNT_TIMEOUT);
+        (std::time::Duration::from_secs_f32(slow), std::time::Duration::from_secs_f32(timeout))
     }
 
-    pub fn get_canonical_name(&self, proc: &str) -> Option<&String> {
-        self.rename.dictionary.get(proc)
+    pub fn is_selected_mount(&self, path: &str, fs_type: &str) -> bool {
+        let Mounts { points, types, .. } = &self.mounts;
+        if points.is_empty() && types.is_empty() {
+            return DEFAULT_MOUNT_TYPES.contains(&fs_type);
+        }
+        points.iter().any(|point| point == path) || types.iter().any(|ty| ty == fs_type)
     }
 }
 
@@ -40,6 +208,12 @@ pub enum ParseConfigError {
     ExpectedRenameArrow(usize),
     UnknownKeyword(usize, String),
     EmptyRest(usize),
+    InvalidSeconds(usize, String),
+    InvalidPattern(usize, String),
+    InvalidUidRange(usize, String),
+    InvalidPercentage(usize, String),
+    InvalidSize(usize, String),
+    InvalidCount(usize, String),
 }
 
 impl std::fmt::Display for ParseConfigError {
@@ -57,6 +231,27 @@ impl std::fmt::Display for ParseConfigError {
             ParseConfigError::EmptyRest(ln) => {
                 write!(f, "expected additional information on line {ln}")
             }
+            ParseConfigError::InvalidSeconds(ln, value) => {
+                write!(f, "expected a positive number of seconds on line {ln}, but found {value:?}")
+            }
+            ParseConfigError::InvalidPattern(ln, e) => {
+                write!(f, "encountered an invalid pattern on line {ln}: {e}")
+            }
+            ParseConfigError::InvalidUidRange(ln, value) => {
+                write!(
+                    f,
+                    "expected a user id or a range of user ids on line {ln}, but found {value:?}"
+                )
+            }
+            ParseConfigError::InvalidPercentage(ln, value) => {
+                write!(f, "expected a percentage on line {ln}, but found {value:?}")
+            }
+            ParseConfigError::InvalidSize(ln, value) => {
+                write!(f, "expected a size such as 512M or 4G on line {ln}, but found {value:?}")
+            }
+            ParseConfigError::InvalidCount(ln, value) => {
+                write!(f, "expected a positive whole number on line {ln}, but found {value:?}")
+            }
         }
     }
 }
@@ -69,7 +264,24 @@ impl FromStr for Config {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut processes = Vec::new();
         let mut users = Vec::new();
-        let mut rename = HashMap::new();
+        let mut uids = Vec::new();
+        let mut rename = Vec::new();
+        let mut mount_points = Vec::new();
+        let mut mount_types = Vec::new();
+        let mut gpu_queries = GpuQueries::default();
+        let mut mount_slow = None;
+        let mut mount_timeout = None;
+        let mut thresholds = Thresholds::default();
+        let seconds = |ln: usize, rest: &str| match rest.parse::<f32>() {
+            Ok(seconds) if seconds.is_finite() && seconds > 0.0 => Ok(seconds),
+            _ => Err(ParseConfigError::InvalidSeconds(ln, rest.to_string())),
+        };
+        let pattern = |ln: usize, rest: &str| {
+            Pattern::parse(rest).map_err(|e| ParseConfigError::InvalidPattern(ln, e.to_string()))
+        };
+        let uid_range = |ln: usize, rest: &str| {
+            parse_uid_range(rest).ok_or(ParseConfigError::InvalidUidRange(ln, rest.to_string()))
+        };
 
         let lines = s.lines();
         for (ln, line) in lines.enumerate() {
@@ -101,13 +313,41 @@ impl FromStr for Config {
             }
 
             match keyword {
-                "ignore-user" => users.push(rest.to_string()),
-                "ignore-proc" => processes.push(rest.to_string()),
+                "ignore-user" => users.push(pattern(ln, rest)?),
+                "ignore-uid" => uids.push(uid_range(ln, rest)?),
+                "ignore-proc" => processes.push(pattern(ln, rest)?),
                 "rename-proc" => {
-                    let Some((from, to)) = rest.split_once("->") else {
+                    // A regular expression may contain an arrow itself, but the replacement may not.
+                    let Some((from, to)) = rest.rsplit_once("->") else {
                         return Err(Self::Err::ExpectedRenameArrow(ln));
                     };
-                    rename.insert(from.trim().to_string(), to.trim().to_string());
+                    rename.push((pattern(ln, from.trim())?, to.trim().to_string()));
+                }
+                "mount-point" => mount_points.push(rest.to_string()),
+                "mount-type" => mount_types.push(rest.to_string()),
+                "gpu-query" => gpu_queries.gpus = Some(rest.to_string()),
+                "gpu-process-query" => gpu_queries.processes = Some(rest.to_string()),
+                "mount-slow" => mount_slow = Some(seconds(ln, rest)?),
+                "mount-timeout" => mount_timeout = Some(seconds(ln, rest)?),
+                "cpu-threshold" => {
+                    thresholds.cpu = match rest.trim_end_matches('%').trim().parse::<f32>() {
+                        Ok(cpu) if cpu.is_finite() && cpu >= 0.0 => cpu,
+                        _ => return Err(Self::Err::InvalidPercentage(ln, rest.to_string())),
+                    }
+                }
+                "mem-threshold" => {
+                    let mem = parse_size(rest);
+                    thresholds.mem = Some(mem.ok_or(Self::Err::InvalidSize(ln, rest.to_string()))?);
+                }
+                "io-threshold" => {
+                    let io = parse_size(rest.strip_suffix("/s").unwrap_or(rest).trim_end());
+                    thresholds.io = Some(io.ok_or(Self::Err::InvalidSize(ln, rest.to_string()))?);
+                }
+                "max-processes" => {
+                    thresholds.max_processes = match rest.parse::<u32>() {
+                        Ok(max) if max > 0 => Some(max),
+                        _ => return Err(Self::Err::InvalidCount(ln, rest.to_string())),
+                    }
                 }
                 unknown => return Err(Self::Err::UnknownKeyword(ln, unknown.to_string())),
             }
@@ -117,8 +357,238 @@ impl FromStr for Config {
             ignore: Ignore {
                 processes: processes.into_boxed_slice(),
                 users: users.into_boxed_slice(),
+                uids: uids.into_boxed_slice(),
+            },
+            rename: Rename { rules: rename.into_boxed_slice() },
+            mounts: Mounts {
+                points: mount_points.into_boxed_slice(),
+                types: mount_types.into_boxed_slice(),
+                slow: mount_slow,
+                timeout: mount_timeout,
             },
-            rename: Rename { dictionary: rename },
+            gpu_queries,
+            thresholds,
         })
     }
 }
+
+/// Parses a user id, such as `65534`, or an inclusive range of them, such as `0-999`.
+///
+/// Either end of a range may be left open, as in `60000-`, to reach to the first or last user id.
+fn parse_uid_range(s: &str) -> Option<RangeInclusive<u32>> {
+    let Some((start, end)) = s.split_once('-') else {
+        let uid = s.parse().ok()?;
+        return Some(uid..=uid);
+    };
+    if start.trim().is_empty() && end.trim().is_empty() {
+        return None;
+    }
+    let bound = |s: &str, open| match s.trim() {
+        "" => Some(open),
+        s => s.parse::<u32>().ok(),
+    };
+    let (start, end) = (bound(start, u32::MIN)?, bound(end, u32::MAX)?);
+    (start <= end).then_some(start..=end)
+}
+
+/// Parses a size in bytes with an optional binary unit, such as `512M`, `4G`, or `1.5 GiB`.
+fn parse_size(s: &str) -> Option<u64> {
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number = number.parse::<f64>().ok()?;
+    let unit = unit.trim().to_ascii_uppercase();
+    let unit = unit.strip_suffix("IB").or_else(|| unit.strip_suffix('B')).unwrap_or(&unit);
+    let exponent = match unit {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return None,
+    };
+    let bytes = number * 1024f64.powi(exponent);
+    // The conversion would saturate rather than fail for sizes that do not fit.
+    (bytes < u64::MAX as f64).then_some(bytes as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(s: &str) -> Config {
+        Config::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn matches_exact_names_globs_and_regular_expressions() {
+        let config = config(
+            "ignore-proc: tracker\n\
+             ignore-proc: gvfs-*\n\
+             ignore-proc: /^kworker/\n",
+        );
+        let ignored = |name| config.is_ignored_process(name, name);
+        assert!(ignored("tracker"));
+        assert!(!ignored("tracker-miner-fs"));
+        assert!(!ignored("my-tracker"));
+        assert!(ignored("gvfs-udisks2-volume-monitor"));
+        assert!(ignored("gvfs-"));
+        assert!(!ignored("gvfsd"));
+        // A regular expression matches any part of a name, unless it is anchored.
+        assert!(ignored("kworker/0:1"));
+        assert!(!ignored("not-a-kworker"));
+    }
+
+    #[test]
+    fn escapes_regex_characters_in_globs() {
+        let config = config(
+            "ignore-proc: python3.1?\n\
+             ignore-proc: g++-*\n\
+             ignore-proc: worker[0-9]\n\
+             ignore-proc: [!a]sh\n",
+        );
+        let ignored = |name| config.is_ignored_process(name, name);
+        assert!(ignored("python3.11"));
+        assert!(!ignored("python3x11"));
+        assert!(!ignored("python3.111"));
+        assert!(ignored("g++-12"));
+        assert!(!ignored("ggg-12"));
+        assert!(ignored("worker7"));
+        assert!(!ignored("workerX"));
+        assert!(ignored("zsh"));
+        assert!(!ignored("ash"));
+    }
+
+    #[test]
+    fn reports_the_line_of_an_invalid_pattern() {
+        let err =
+            Config::from_str("# Comment\nignore-proc: ok\nignore-proc: /(unclosed/\n").unwrap_err();
+        assert!(matches!(err, ParseConfigError::InvalidPattern(3, _)), "{err:?}");
+        assert!(err.to_string().contains("on line 3"), "{err}");
+        let err = Config::from_str("rename-proc: /[/ -> x\n").unwrap_err();
+        assert!(matches!(err, ParseConfigError::InvalidPattern(1, _)), "{err:?}");
+    }
+
+    #[test]
+    fn parses_uid_ranges() {
+        assert_eq!(parse_uid_range("65534"), Some(65534..=65534));
+        assert_eq!(parse_uid_range("0-999"), Some(0..=999));
+        assert_eq!(parse_uid_range("100 - 200"), Some(100..=200));
+        assert_eq!(parse_uid_range("60000-"), Some(60000..=u32::MAX));
+        assert_eq!(parse_uid_range("-999"), Some(0..=999));
+        for malformed in ["", "-", "abc", "999-0", "1-2-3", "1-x", "-1-5", "4294967296"] {
+            assert_eq!(parse_uid_range(malformed), None, "{malformed:?}");
+        }
+
+        let config = config("ignore-uid: 1-999\nignore-uid: 65534\n");
+        assert!(config.is_ignored_user("daemon", Some(1)));
+        assert!(config.is_ignored_user("nobody", Some(65534)));
+        assert!(!config.is_ignored_user("root", Some(0)));
+        assert!(!config.is_ignored_user("marieke", Some(1000)));
+        assert!(!config.is_ignored_user("daemon", None));
+        let err = Config::from_str("ignore-uid: 999-1\n").unwrap_err();
+        assert!(matches!(err, ParseConfigError::InvalidUidRange(1, _)), "{err:?}");
+    }
+
+    #[test]
+    fn renames_with_the_first_matching_rule() {
+        let config = config(
+            "rename-proc: /^python3\\.\\d+$/ -> python\n\
+             rename-proc: python3.12 -> py312\n\
+             rename-proc: /^(\\w+)-worker$/ -> $1\n\
+             rename-proc: /a->b/ -> arrow\n\
+             rename-proc: tracker-* -> tracker\n",
+        );
+        let renamed = |name, comm| config.get_canonical_name(name, comm);
+        assert_eq!(renamed("python3.12", "python3.12").as_deref(), Some("python"));
+        assert_eq!(renamed("celery-worker", "celery-worker").as_deref(), Some("celery"));
+        // The replacement is split off at the last arrow.
+        assert_eq!(renamed("a->b", "a->b").as_deref(), Some("arrow"));
+        // A rule is tried on both names before the next rule is.
+        assert_eq!(renamed("tracker-miner-fs-3", "python3.11").as_deref(), Some("python"));
+        assert_eq!(renamed("tracker-miner-fs-3", "tracker-miner-f").as_deref(), Some("tracker"));
+        assert_eq!(renamed("bash", "bash"), None);
+    }
+
+    #[test]
+    fn keeps_matching_the_shipped_rules() {
+        let config = config(include_str!("../../../config/mu-bee/ignore.linus"));
+        assert!(config.is_ignored_user("root", Some(0)));
+        assert!(config.is_ignored_user("+", None));
+        assert!(!config.is_ignored_user("marieke", Some(1000)));
+        assert!(config.is_ignored_process("gsd-housekeeping", "gsd-housekeepin"));
+        assert!(config.is_ignored_process("tracker-miner-fs-3", "tracker-miner-f"));
+        assert!(config.is_ignored_process("pool-tracker-mi", "pool-tracker-mi"));
+        assert!(!config.is_ignored_process("systemd-journald", "systemd-journal"));
+        assert_eq!(config.get_canonical_name("vmd_LINUXAMD64", "vmd_LINUXAMD64").unwrap(), "vmd");
+        assert_eq!(config.get_canonical_name("firefox", "Isolated Web Co").unwrap(), "(browser)");
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("0"), Some(0));
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("512B"), Some(512));
+        assert_eq!(parse_size("4K"), Some(4096));
+        assert_eq!(parse_size("512M"), Some(512 << 20));
+        assert_eq!(parse_size("4G"), Some(4 << 30));
+        assert_eq!(parse_size("1.5 GiB"), Some(3 << 29));
+        assert_eq!(parse_size("2tb"), Some(2 << 40));
+        assert_eq!(parse_size("16gib"), Some(16 << 30));
+        for invalid in ["", "G", "4X", "4 GG", "4Gi", "1.2.3M", "-1G", "four"] {
+            assert_eq!(parse_size(invalid), None, "{invalid:?}");
+        }
+        // Sizes that do not fit are rejected rather than cut off.
+        assert_eq!(parse_size("16777216T"), None);
+        assert_eq!(parse_size("99999999999999999999"), None);
+    }
+
+    #[test]
+    fn parses_thresholds() {
+        let config = config(
+            "cpu-threshold: 25%\n\
+             mem-threshold: 4G\n\
+             io-threshold: 50M/s\n\
+             max-processes: 20\n",
+        );
+        let Thresholds { cpu, mem, io, max_processes } = config.thresholds;
+        assert_eq!(cpu, 25.0);
+        assert_eq!(mem, Some(4 << 30));
+        assert_eq!(io, Some(50 << 20));
+        assert_eq!(max_processes, Some(20));
+        assert_eq!(self::config("cpu-threshold: 2.5").thresholds.cpu, 2.5);
+        assert_eq!(self::config("io-threshold: 1K /s").thresholds.io, Some(1024));
+
+        let defaults = self::config("");
+        assert_eq!(defaults.thresholds.cpu, mu::model::PROCESS_USAGE_THRESHOLD_PERCENT);
+        assert_eq!(defaults.thresholds.mem, None);
+    }
+
+    #[test]
+    fn reports_invalid_thresholds() {
+        let err = |s: &str| Config::from_str(s).unwrap_err();
+        assert!(matches!(err("cpu-threshold: -5"), ParseConfigError::InvalidPercentage(1, _)));
+        assert!(matches!(err("cpu-threshold: lots%"), ParseConfigError::InvalidPercentage(1, _)));
+        assert!(matches!(err("cpu-threshold: inf"), ParseConfigError::InvalidPercentage(1, _)));
+        assert!(matches!(err("\nmem-threshold: 4X"), ParseConfigError::InvalidSize(2, _)));
+        assert!(matches!(err("mem-threshold: 16777216T"), ParseConfigError::InvalidSize(1, _)));
+        assert!(matches!(err("io-threshold: 50M/h"), ParseConfigError::InvalidSize(1, _)));
+        assert!(matches!(err("max-processes: 0"), ParseConfigError::InvalidCount(1, _)));
+        assert!(matches!(err("max-processes: -3"), ParseConfigError::InvalidCount(1, _)));
+        assert!(matches!(err("mem-threshold:"), ParseConfigError::EmptyRest(1)));
+        assert!(matches!(err("mem-threshold"), ParseConfigError::ExpectedColon(1)));
+        assert!(matches!(err("mem-limit: 4G"), ParseConfigError::UnknownKeyword(1, _)));
+
+        assert_eq!(
+            err("# Memory\nmem-threshold: 4 gigs").to_string(),
+            "expected a size such as 512M or 4G on line 2, but found \"4 gigs\""
+        );
+        assert_eq!(
+            err("cpu-threshold: half").to_string(),
+            "expected a percentage on line 1, but found \"half\""
+        );
+        assert_eq!(
+            err("max-processes: 0").to_string(),
+            "expected a positive whole number on line 1, but found \"0\""
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Usage of the GPUs, as reported by vendor query commands.
+//!
+//! The bee does not talk to GPU drivers itself. Instead, it runs two configurable commands and
+//! parses their output as comma-separated values:
+//!
+//! - `gpu-query` lists the GPUs, one per line, with the columns
+//!   `index, uuid, name, utilization [%], memory.used [MiB], memory.total [MiB]`.
+//! - `gpu-process-query` lists the processes running on them, with the columns
+//!   `gpu_uuid, pid, used_memory [MiB]`.
+//!
+//! These are the columns produced by
+//!
+//! ```sh
+//! nvidia-smi --query-gpu=index,uuid,name,utilization.gpu,memory.used,memory.total --format=csv
+//! nvidia-smi --query-compute-apps=gpu_uuid,pid,used_memory --format=csv
+//! ```
+//!
+//! Header lines, units, and `[N/A]` values are tolerated, so the `noheader` and `nounits` format
+//! options are optional. Other vendors can be supported through a small wrapper script that
+//! produces the same columns.
+//!
+//! Recorded output and a stub script standing in for `nvidia-smi` can be found in
+//! `config/mu-bee/gpu`, for trying this out on machines without a GPU.
+
+use std::time::Duration;
+
+use mu::model::{Gpu, GpuProcess, Memory};
+
+use crate::command::run_query;
+use crate::config::Config;
+
+/// Time a query command may take before we give up on it.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A GPU, as listed by the `gpu-query` command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuRow {
+    pub index: u32,
+    pub uuid: String,
+    pub name: String,
+    pub utilization: Option<f32>,
+    /// Used memory in bytes.
+    pub mem_used: Option<u64>,
+    /// Total memory in bytes.
+    pub mem_total: Option<u64>,
+}
+
+/// A process, as listed by the `gpu-process-query` command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuProcessRow {
+    pub gpu_uuid: String,
+    pub pid: u32,
+    /// Used memory in bytes.
+    pub mem: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseGpuError {
+    ColumnCount { ln: usize, expected: usize, found: usize },
+    InvalidValue { ln: usize, column: &'static str, value: String },
+}
+
+impl std::fmt::Display for ParseGpuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseGpuError::ColumnCount { ln, expected, found } => {
+                write!(f, "expected {expected} columns on line {ln}, but found {found}")
+            }
+            ParseGpuError::InvalidValue { ln, column, value } => {
+                write!(f, "could not parse {value:?} as the {column} on line {ln}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseGpuError {}
+
+/// Parses the output of the `gpu-query` command.
+pub fn parse_gpus(s: &str) -> Result<Vec<GpuRow>, ParseGpuError> {
+    let mut gpus = Vec::new();
+    for (ln, fields) in rows(s, "index") {
+        let [index, uuid, name, utilization, mem_used, mem_total] = columns(ln, &fields)?;
+        gpus.push(GpuRow {
+            index: number(ln, "index", index)?.ok_or_else(|| invalid(ln, "index", index))?,
+            uuid: uuid.to_string(),
+            name: name.to_string(),
+            utilization: number(ln, "utilization", utilization)?,
+            mem_used: number(ln, "used memory", mem_used)?.map(mebibytes),
+            mem_total: number(ln, "total memory", mem_total)?.map(mebibytes),
+        });
+    }
+    Ok(gpus)
+}
+
+/// Parses the output of the `gpu-process-query` command.
+pub fn parse_gpu_processes(s: &str) -> Result<Vec<GpuProcessRow>, ParseGpuError> {
+    let mut processes = Vec::new();
+    for (ln, fields) in rows(s, "gpu_uuid") {
+        // Some versions of nvidia-smi report the absence of processes in prose.
+        if let [line] = fields.as_slice()
+            && line.starts_with("No running")
+        {
+            continue;
+        }
+        let [gpu_uuid, pid, mem] = columns(ln, &fields)?;
+        processes.push(GpuProcessRow {
+            gpu_uuid: gpu_uuid.to_string(),
+            pid: number(ln, "pid", pid)?.ok_or_else(|| invalid(ln, "pid", pid))?,
+            mem: number(ln, "used memory", mem)?.map(mebibytes),
+        });
+    }
+    Ok(processes)
+}
+
+/// Returns the line numbers and fields of the non-empty lines of `s`, skipping the header line.
+///
+/// A header line is recognized by its first field, which is the name of the first column.
+fn rows<'s>(s: &'s str, first_column: &str) -> impl Iterator<Item = (usize, Vec<&'s str>)> {
+    s.lines()
+        .enumerate()
+        .map(|(ln, line)| (ln + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(ln, line)| (ln, line.split(',').map(str::trim).collect::<Vec<_>>()))
+        .filter(move |(_, fields)| !fields[0].eq_ignore_ascii_case(first_column))
+}
+
+fn columns<'f, const N: usize>(
+    ln: usize,
+    fields: &[&'f str],
+) -> Result<[&'f str; N], ParseGpuError> {
+    fields.try_into().map_err(|_| ParseGpuError::ColumnCount {
+        ln,
+        expected: N,
+        found: fields.len(),
+    })
+}
+
+/// Parses a number, possibly followed by a unit, such as `42 %` or `1024 MiB`.
+///
+/// Values that are not available, such as `[N/A]` or `[Not Supported]`, are returned as [`None`].
+fn number<T: std::str::FromStr>(
+    ln: usize,
+    column: &'static str,
+    value: &str,
+) -> Result<Option<T>, ParseGpuError> {
+    let unavailable = value.starts_with('[') || value.eq_ignore_ascii_case("N/A");
+    if unavailable {
+        return Ok(None);
+    }
+    let number = value.split_whitespace().next().unwrap_or_default();
+    number.parse().map(Some).map_err(|_| invalid(ln, column, value))
+}
+
+fn invalid(ln: usize, column: &'static str, value: &str) -> ParseGpuError {
+    ParseGpuError::InvalidValue { ln, column, value: value.to_string() }
+}
+
+fn mebibytes(mib: u64) -> u64 {
+    mib * 1024 * 1024
+}
+
+/// Runs the GPU queries from the `config` and gathers the usage of the GPUs.
+///
+/// The names and users of the processes on the GPUs are looked up in `system`.
+pub fn gather(system: &sysinfo::System, users: &sysinfo::Users, config: &Config) -> Box<[Gpu]> {
+    let Some(gpu_query) = &config.gpu_queries.gpus else {
+        return Box::default();
+    };
+    let gpus = match run_query(gpu_query, QUERY_TIMEOUT) {
+        Ok(output) => parse_gpus(&output).map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    let gpus = match gpus {
+        Ok(gpus) => gpus,
+        Err(e) => {
+            eprintln!("WARNING: Could not query the GPUs: {e}");
+            return Box::default();
+        }
+    };
+
+    let processes = match &config.gpu_queries.processes {
+        Some(process_query) => match run_query(process_query, QUERY_TIMEOUT) {
+            Ok(output) => parse_gpu_processes(&output).unwrap_or_else(|e| {
+                eprintln!("WARNING: Could not parse the GPU processes: {e}");
+                Vec::new()
+            }),
+            Err(e) => {
+                eprintln!("WARNING: Could not query the GPU processes: {e}");
+                Vec::new()
+            }
+        },
+        None => Vec::new(),
+    };
+
+    gpus.into_iter()
+        .map(|gpu| {
+            let processes = processes
+                .iter()
+                .filter(|process| process.gpu_uuid == gpu.uuid)
+                .map(|process| {
+                    let proc = system.process(sysinfo::Pid::from_u32(process.pid));
+                    let (name, comm) = proc
+                        .map(crate::model::process_names)
+                        .unwrap_or(("?".to_string(), "?".to_string()));
+                    let name = config.get_canonical_name(&name, &comm).unwrap_or(name);
+                    let user = proc
+                        .and_then(|proc| proc.effective_user_id().or(proc.user_id()))
+                        .and_then(|uid| users.get_user_by_id(uid))
+                        .map(|user| user.name())
+                        .unwrap_or("?")
+                        .to_string();
+                    GpuProcess { pid: process.pid, name, user, mem: process.mem }
+                })
+                .collect();
+            Gpu {
+                index: gpu.index,
+                name: gpu.name,
+                utilization: gpu.utilization,
+                mem: Memory {
+                    total: gpu.mem_total.unwrap_or_default(),
+                    used: gpu.mem_used.unwrap_or_default(),
+                    available: None,
+                },
+                processes,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const GPUS: &str = include_str!("../../../config/mu-bee/gpu/nvidia-smi-gpus.csv");
+    const APPS: &str = include_str!("../../../config/mu-bee/gpu/nvidia-smi-apps.csv");
+    const STUB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/config/mu-bee/gpu/nvidia-smi-stub.sh");
+
+    fn gather_with(queries: &str) -> Box<[Gpu]> {
+        let config = Config::from_str(queries).unwrap();
+        gather(&sysinfo::System::new(), &sysinfo::Users::new(), &config)
+    }
+
+    #[test]
+    fn parses_recorded_gpus() {
+        let gpus = parse_gpus(GPUS).unwrap();
+        assert_eq!(gpus.len(), 3);
+        assert_eq!(gpus[0].index, 0);
+        assert_eq!(gpus[0].uuid, "GPU-5f1c7a3e-8f2b-4c1d-9a6e-2b7d3c4e5f60");
+        assert_eq!(gpus[0].name, "NVIDIA GeForce RTX 3090");
+        assert_eq!(gpus[0].utilization, Some(97.0));
+        assert_eq!(gpus[0].mem_used, Some(mebibytes(20113)));
+        assert_eq!(gpus[0].mem_total, Some(mebibytes(24576)));
+        assert_eq!(gpus[2].index, 2);
+        assert_eq!(gpus[2].utilization, None);
+        assert_eq!(gpus[2].mem_used, Some(mebibytes(1210)));
+        assert_eq!(gpus[2].mem_total, Some(mebibytes(12288)));
+    }
+
+    #[test]
+    fn parses_recorded_processes() {
+        let processes = parse_gpu_processes(APPS).unwrap();
+        assert_eq!(processes.len(), 3);
+        assert_eq!(processes[0].gpu_uuid, "GPU-5f1c7a3e-8f2b-4c1d-9a6e-2b7d3c4e5f60");
+        assert_eq!(processes[0].pid, 48213);
+        assert_eq!(processes[0].mem, Some(mebibytes(19882)));
+        assert_eq!(processes[2].pid, 51002);
+    }
+
+    #[test]
+    fn parses_without_header_or_units() {
+        let gpus = parse_gpus("3, GPU-x, Tesla T4, 12, 100, [Not Supported]\n").unwrap();
+        assert_eq!(gpus[0].index, 3);
+        assert_eq!(gpus[0].utilization, Some(12.0));
+        assert_eq!(gpus[0].mem_used, Some(mebibytes(100)));
+        assert_eq!(gpus[0].mem_total, None);
+        assert!(parse_gpu_processes("No running processes found\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_rows() {
+        assert!(matches!(
+            parse_gpus("0, GPU-x, Tesla T4, 12 %\n"),
+            Err(ParseGpuError::ColumnCount { ln: 1, expected: 6, found: 4 })
+        ));
+        assert!(matches!(
+            parse_gpu_processes("GPU-x, many, 3 MiB\n"),
+            Err(ParseGpuError::InvalidValue { ln: 1, column: "pid", .. })
+        ));
+    }
+
+    #[test]
+    fn gathers_from_stub() {
+        let gpus = gather_with(&format!(
+            "gpu-query: {STUB} --query-gpu=index,uuid,name,utilization.gpu,memory.used,memory.total --format=csv\n\
+             gpu-process-query: {STUB} --query-compute-apps=gpu_uuid,pid,used_memory --format=csv\n"
+        ));
+        assert_eq!(gpus.len(), 3);
+        assert_eq!(gpus[0].processes.len(), 2);
+        assert_eq!(gpus[1].processes.len(), 0);
+        assert_eq!(gpus[2].processes.len(), 1);
+        // The recorded pids do not belong to any process on this machine.
+        assert_eq!(gpus[0].processes[0].user, "?");
+    }
+
+    #[test]
+    fn failing_queries_give_no_gpus() {
+        assert!(gather_with("gpu-query: /nonexistent/nvidia-smi\n").is_empty());
+        assert!(gather_with(&format!("gpu-query: {STUB} --unsupported\n")).is_empty());
+        assert!(gather_with("gpu-query: exit 1\n").is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+//! The hardware inventory of the machine, from sysinfo and `/sys/devices/system/cpu`.
+//!
+//! The topology of each logical CPU is in `cpuN/topology`, where `physical_package_id` names its
+//! socket and `core_id` its core within that socket. Logical CPUs that share both are hyperthreads
+//! of the same physical core. The highest frequency of a core is in `cpuN/cpufreq`, which is
+//! missing on many virtual machines.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use mu::model::{Inventory, unix_timestamp};
+
+pub const CPU_DIR: &str = "/sys/devices/system/cpu";
+
+/// Places where the machine id may be kept, in order of preference.
+const MACHINE_ID_PATHS: &[&str] = &["/etc/machine-id", "/var/lib/dbus/machine-id"];
+
+/// Gathers the inventory of the machine. The CPUs and memory of `system` must be refreshed.
+pub fn gather(system: &sysinfo::System) -> Inventory {
+    let cpus = system.cpus();
+    let names = cpus.iter().map(|cpu| cpu.name()).collect::<Vec<_>>();
+    let (sockets, physical_cores, core_of_cpu) = match topology(Path::new(CPU_DIR), &names) {
+        Some(Topology { sockets, physical_cores, core_of_cpu }) => {
+            (Some(sockets), Some(physical_cores), core_of_cpu)
+        }
+        None => {
+            eprintln!("WARNING: Could not determine the topology of the CPUs from {CPU_DIR:?}.");
+            let physical_cores = sysinfo::System::physical_core_count().map(|n| n as u32);
+            (None, physical_cores, Box::default())
+        }
+    };
+    Inventory {
+        gathered: unix_timestamp(),
+        cpu_model: cpus.first().map(|cpu| cpu.brand().trim().to_string()).unwrap_or_default(),
+        sockets,
+        physical_cores,
+        logical_cores: cpus.len() as u32,
+        core_of_cpu,
+        max_frequency: max_frequency(cpus),
+        memory: system.total_memory(),
+        os: sysinfo::System::long_os_version(),
+        kernel_version: sysinfo::System::kernel_version(),
+        boot_time: sysinfo::System::boot_time(),
+        machine_id: MACHINE_ID_PATHS.iter().find_map(|path| {
+            let id = std::fs::read_to_string(path).ok()?;
+            Some(id.trim().to_string()).filter(|id| !id.is_empty())
+        }),
+    }
+}
+
+/// Returns the highest frequency in MHz that any of the `cpus` can run at, if known.
+pub fn max_frequency(cpus: &[sysinfo::Cpu]) -> Option<u64> {
+    cpufreq(Path::new(CPU_DIR), cpus.iter().map(|cpu| cpu.name()), "cpuinfo_max_freq")
+}
+
+/// Returns the base frequency in MHz of the `cpus`, if the CPU driver reports it.
+///
+/// Only some drivers, such as `intel_pstate`, know the base frequency of a CPU.
+pub fn base_frequency(cpus: &[sysinfo::Cpu]) -> Option<u64> {
+    cpufreq(Path::new(CPU_DIR), cpus.iter().map(|cpu| cpu.name()), "base_frequency")
+}
+
+/// Returns the highest of the frequencies in the `cpufreq` `file` of the CPUs with the given
+/// `names` in `cpu_dir`, in MHz.
+fn cpufreq<'n>(cpu_dir: &Path, names: impl Iterator<Item = &'n str>, file: &str) -> Option<u64> {
+    names
+        .filter_map(|name| read::<u64>(&cpu_dir.join(name).join("cpufreq").join(file)))
+        // The kernel reports the frequency in kHz.
+        .map(|khz| khz / 1000)
+        .max()
+}
+
+/// How the logical CPUs are laid out over sockets and physical cores.
+#[derive(Debug, PartialEq, Eq)]
+struct Topology {
+    sockets: u32,
+    physical_cores: u32,
+    /// The physical core of each logical CPU, numbered by its position among all distinct cores.
+    core_of_cpu: Box<[u32]>,
+}
+
+/// Reads the topology of the logical CPUs with the given `names` from `cpu_dir`, if it is known for
+/// all of them.
+fn topology(cpu_dir: &Path, names: &[&str]) -> Option<Topology> {
+    let cores = names.iter().map(|name| core(cpu_dir, name)).collect::<Option<Vec<_>>>()?;
+    let sockets = cores.iter().map(|&(socket, _)| socket).collect::<BTreeSet<_>>();
+    let distinct = cores.iter().copied().collect::<BTreeSet<_>>();
+    let core_of_cpu = cores
+        .iter()
+        .map(|core| distinct.iter().position(|c| c == core).unwrap_or_default() as u32)
+        .collect();
+    Some(Topology {
+        sockets: sockets.len() as u32,
+        physical_cores: distinct.len() as u32,
+        core_of_cpu,
+    })
+}
+
+/// Returns the socket and the core within that socket of the logical CPU called `name`, such as
+/// `cpu3`, if both are known.
+///
+/// Architectures that do not know them report `-1`, which would make all CPUs look like a single
+/// core.
+fn core(cpu_dir: &Path, name: &str) -> Option<(u32, u32)> {
+    let dir = cpu_dir.join(name).join("topology");
+    let id = |file: &str| u32::try_from(read::<i64>(&dir.join(file))?).ok();
+    Some((id("physical_package_id")?, id("core_id")?))
+}
+
+pub fn read<T: std::str::FromStr>(path: &Path) -> Option<T> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Recorded CPU directories, in the layout of `/sys/devices/system/cpu`.
+    pub const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/config/mu-bee/cpu");
+
+    fn names(n: usize) -> Vec<String> {
+        (0..n).map(|n| format!("cpu{n}")).collect()
+    }
+
+    #[test]
+    fn counts_the_physical_cores() {
+        // Two sockets of two cores with two hyperthreads each. The core ids skip numbers and are
+        // reused between the sockets, as on many Xeons.
+        let names = names(8);
+        let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+        let topology = topology(&Path::new(FIXTURES).join("xeon"), &names).unwrap();
+        assert_eq!(topology.sockets, 2);
+        assert_eq!(topology.physical_cores, 4);
+        assert_eq!(&*topology.core_of_cpu, [0, 1, 2, 3, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn leaves_an_unknown_topology_unknown() {
+        // A virtual machine that reports -1 for its socket.
+        let dir = Path::new(FIXTURES).join("arm-vm");
+        assert_eq!(core(&dir, "cpu0"), None);
+        assert_eq!(topology(&dir, &["cpu0", "cpu1"]), None);
+        // So is a CPU whose topology is missing altogether.
+        let dir = Path::new(FIXTURES).join("xeon");
+        assert_eq!(core(&dir, "cpu5"), Some((0, 4)));
+        assert_eq!(topology(&dir, &["cpu0", "cpu8"]), None);
+    }
+
+    #[test]
+    fn reads_the_frequencies() {
+        let xeon = Path::new(FIXTURES).join("xeon");
+        let names = ["cpu0", "cpu1"];
+        assert_eq!(cpufreq(&xeon, names.into_iter(), "cpuinfo_max_freq"), Some(3700));
+        assert_eq!(cpufreq(&xeon, names.into_iter(), "base_frequency"), Some(2100));
+        // Without a driver that knows the base frequency, only the maximum is there.
+        let vm = Path::new(FIXTURES).join("arm-vm");
+        assert_eq!(cpufreq(&vm, names.into_iter(), "cpuinfo_max_freq"), Some(2400));
+        assert_eq!(cpufreq(&vm, names.into_iter(), "base_frequency"), None);
+    }
+}
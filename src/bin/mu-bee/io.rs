@@ -0,0 +1,192 @@
+//! Throughput of the network interfaces, block devices, and processes.
+//!
+//! The kernel keeps running totals of the bytes that went through each network interface in
+//! `/proc/net/dev`, and of the sectors read from and written to each block device in
+//! `/proc/diskstats`. We read them at the start and the end of the sampling window, and report the
+//! rates over the window. The totals of a process are in `/proc/<pid>/io`, which only its owner
+//! and root can read.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use mu::model::{DiskIo, NetworkIo};
+
+/// Size of the sectors counted in `/proc/diskstats`, regardless of the device.
+const SECTOR_SIZE: u64 = 512;
+
+/// Directory with an entry for each whole disk.
+const SYS_BLOCK: &str = "/sys/block";
+
+/// Prefixes of block devices that do not stand for storage, such as loop devices and RAM disks.
+const VIRTUAL_DEVICES: &[&str] = &["loop", "ram", "zram"];
+
+/// Running totals of bytes in and out, by interface or device.
+type Totals = BTreeMap<String, (u64, u64)>;
+
+/// The totals of the network interfaces and the block devices at some moment.
+#[derive(Debug)]
+pub struct Counters {
+    networks: std::io::Result<Totals>,
+    disks: std::io::Result<Totals>,
+}
+
+impl Counters {
+    pub fn read() -> Self {
+        Self {
+            networks: std::fs::read_to_string("/proc/net/dev").map(|s| parse_net_dev(&s)),
+            disks: std::fs::read_to_string("/proc/diskstats")
+                .map(|s| parse_diskstats(&s, Path::new(SYS_BLOCK))),
+        }
+    }
+}
+
+/// Returns the throughput of the network interfaces and the block devices between two readings of
+/// their counters, `elapsed` apart.
+pub fn rates(
+    before: &Counters,
+    after: &Counters,
+    elapsed: Duration,
+) -> (Box<[NetworkIo]>, Box<[DiskIo]>) {
+    let networks = difference("/proc/net/dev", &before.networks, &after.networks, elapsed)
+        .into_iter()
+        .map(|(interface, (received, transmitted))| NetworkIo { interface, received, transmitted })
+        .collect();
+    let disks = difference("/proc/diskstats", &before.disks, &after.disks, elapsed)
+        .into_iter()
+        .map(|(device, (read, written))| DiskIo { device, read, written })
+        .collect();
+    (networks, disks)
+}
+
+/// Returns the rates per second of the totals that were present in both readings.
+fn difference(
+    path: &str,
+    before: &std::io::Result<Totals>,
+    after: &std::io::Result<Totals>,
+    elapsed: Duration,
+) -> Vec<(String, (u64, u64))> {
+    let (before, after) = match (before, after) {
+        (Ok(before), Ok(after)) => (before, after),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("WARNING: Could not read {path:?}: {e}");
+            return Vec::new();
+        }
+    };
+    after
+        .iter()
+        .filter_map(|(name, &(later_in, later_out))| {
+            let &(earlier_in, earlier_out) = before.get(name)?;
+            let rates =
+                (rate(later_in, earlier_in, elapsed), rate(later_out, earlier_out, elapsed));
+            Some((name.clone(), rates))
+        })
+        .collect()
+}
+
+/// Returns the rate per second of the growth of a running total from `earlier` to `later`.
+pub fn rate(later: u64, earlier: u64, elapsed: Duration) -> u64 {
+    let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+    (later.saturating_sub(earlier) as f64 / seconds) as u64
+}
+
+/// Parses the bytes received and transmitted by each interface from `/proc/net/dev`.
+///
+/// The loopback interface is left out, as are interfaces that never carried any traffic.
+fn parse_net_dev(s: &str) -> Totals {
+    // The first two lines are headers.
+    s.lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (interface, fields) = line.split_once(':')?;
+            let fields = fields.split_whitespace().collect::<Vec<_>>();
+            let received = fields.first()?.parse().ok()?;
+            let transmitted = fields.get(8)?.parse().ok()?;
+            Some((interface.trim().to_string(), (received, transmitted)))
+        })
+        .filter(|(interface, totals)| interface != "lo" && *totals != (0, 0))
+        .collect()
+}
+
+/// Parses the bytes read and written by each block device from `/proc/diskstats`.
+///
+/// Partitions are left out, since their throughput is part of that of their disk. Only whole disks
+/// have a directory in `sys_block`. Virtual devices and devices that never did any I/O are left
+/// out as well.
+fn parse_diskstats(s: &str, sys_block: &Path) -> Totals {
+    s.lines()
+        .filter_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let device = *fields.get(2)?;
+            let read = fields.get(5)?.parse::<u64>().ok()?;
+            let written = fields.get(9)?.parse::<u64>().ok()?;
+            Some((device.to_string(), (read * SECTOR_SIZE, written * SECTOR_SIZE)))
+        })
+        .filter(|(device, totals)| {
+            // The kernel names some devices with a slash, such as `cciss/c0d0`, which becomes an
+            // exclamation mark in their directory name, as in `cciss!c0d0`.
+            let whole = sys_block.join(device.replace('/', "!")).exists();
+            let virtual_device = VIRTUAL_DEVICES.iter().any(|prefix| device.starts_with(prefix));
+            whole && !virtual_device && *totals != (0, 0)
+        })
+        .collect()
+}
+
+/// Returns whether the I/O counters of the process with the given `pid` can be read.
+///
+/// The counters of processes that cannot be read appear to be zero.
+pub fn is_readable(pid: sysinfo::Pid) -> bool {
+    std::fs::File::open(format!("/proc/{pid}/io")).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NET_DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 141498475   13790    0    0    0     0          0         0 141498475   13790    0    0    0     0       0          0
+  eth0: 9120733422 6815123    0   12    0     0          0      1024 385512871 2345678    0    0    0     0       0          0
+  ifb0:       0       0    0    0    0     0          0         0        0       0    0    0    0     0       0          0
+wlp2s0:1048576    2048    0    0    0     0          0         0   524288    1024    0    0    0     0       0          0
+";
+
+    const DISKSTATS: &str = "\
+   7       0 loop0 512 0 8192 10 0 0 0 0 0 20 10 0 0 0 0 0 0
+ 259       0 nvme0n1 183442 51234 12734120 30201 421337 310292 40211768 512003 0 301220 544102 0 0 0 0 9021 1898
+ 259       1 nvme0n1p1 312 1024 14210 51 2 0 2 0 0 88 51 0 0 0 0 0 0
+ 104       0 cciss/c0d0 1000 0 2000 10 3000 0 4000 20 0 40 30 0 0 0 0 0 0
+ 104       1 cciss/c0d0p1 1000 0 2000 10 3000 0 4000 20 0 40 30 0 0 0 0 0 0
+   8      16 sdb 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+";
+
+    #[test]
+    fn parses_net_dev() {
+        let totals = parse_net_dev(NET_DEV);
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals["eth0"], (9120733422, 385512871));
+        // The counters may follow the colon without a space.
+        assert_eq!(totals["wlp2s0"], (1048576, 524288));
+    }
+
+    #[test]
+    fn parses_diskstats() {
+        let sys_block = std::env::temp_dir().join(format!("mu-sys-block-{}", std::process::id()));
+        for disk in ["nvme0n1", "cciss!c0d0", "sdb", "loop0"] {
+            std::fs::create_dir_all(sys_block.join(disk)).unwrap();
+        }
+        let totals = parse_diskstats(DISKSTATS, &sys_block);
+        std::fs::remove_dir_all(&sys_block).unwrap();
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals["nvme0n1"], (12734120 * SECTOR_SIZE, 40211768 * SECTOR_SIZE));
+        assert_eq!(totals["cciss/c0d0"], (2000 * SECTOR_SIZE, 4000 * SECTOR_SIZE));
+    }
+
+    #[test]
+    fn computes_rates() {
+        assert_eq!(rate(3000, 1000, Duration::from_millis(500)), 4000);
+        // A counter that went backwards, as after a device was replaced, has no throughput.
+        assert_eq!(rate(1000, 3000, Duration::from_secs(1)), 0);
+    }
+}
@@ -0,0 +1,221 @@
+//! Jobs, which are groups of processes that were started together.
+//!
+//! The shell puts each command line it runs in a process group of its own, and the processes it
+//! starts inherit that group unless they explicitly break away. The ranks of an `mpirun`, the
+//! rules run by Snakemake, and the compilers of a `make -j` therefore all end up in the group of
+//! the command that started them. We report each process group of a user as a job.
+
+use std::collections::BTreeMap;
+
+use mu::model::{Job, Thresholds};
+
+use crate::config::Config;
+use crate::model::{process_names, sanitize_command};
+use crate::window::Sampled;
+
+/// A process, as far as its job is concerned.
+#[derive(Debug, Clone)]
+struct Member {
+    pid: u32,
+    pgid: u32,
+    user: String,
+    /// Name of the process, after renaming.
+    name: String,
+    command: Option<String>,
+    /// Mean CPU usage over the sampling window, in percent of a single core.
+    usage: f32,
+    rss: u64,
+    /// Unix timestamp of when the process was started, or zero if unknown.
+    start_time: u64,
+}
+
+/// Groups the processes on the system into jobs, and gathers the ones that pass the thresholds
+/// from the `config`.
+///
+/// Ignored users and processes are left out, as are threads, since their usage is already part of
+/// the usage of their process. Processes that exited during the sampling window still count
+/// towards their job, since their process group was looked up when they were first seen.
+pub fn gather(
+    system: &sysinfo::System,
+    sampled: &Sampled,
+    users: &sysinfo::Users,
+    config: &Config,
+) -> Box<[Job]> {
+    let current_pid = sysinfo::get_current_pid().ok();
+
+    let mut members = Vec::new();
+    for proc in system.processes().values() {
+        if proc.thread_kind().is_some() || current_pid == Some(proc.pid()) {
+            continue;
+        }
+        let uid = proc.effective_user_id().or(proc.user_id());
+        let user = uid
+            .and_then(|uid| users.get_user_by_id(uid))
+            .map(|u| u.name())
+            .unwrap_or("?")
+            .to_string();
+        let (name, comm) = process_names(proc);
+        let ignore = config.is_ignored_user(&user, uid.map(|uid| **uid))
+            || config.is_ignored_process(&name, &comm);
+        if ignore {
+            continue;
+        }
+        let Some(pgid) = sampled.pgid(proc).or_else(|| pgid(proc.pid())) else { continue };
+        members.push(Member {
+            pid: proc.pid().as_u32(),
+            pgid,
+            user,
+            name: config.get_canonical_name(&name, &comm).unwrap_or(name),
+            command: sanitize_command(proc.cmd()),
+            usage: sampled.process(proc).mean,
+            rss: proc.memory(),
+            start_time: proc.start_time(),
+        });
+    }
+
+    jobs(members, &config.thresholds).into_boxed_slice()
+}
+
+/// Groups the `members` into jobs by their process group and user, and selects the jobs that pass
+/// the `thresholds`, busiest first.
+///
+/// A job is named after the leader of its process group. If the leader is not among the members,
+/// because it is ignored or has exited, the earliest started member stands in for it.
+fn jobs(members: Vec<Member>, thresholds: &Thresholds) -> Vec<Job> {
+    let mut groups = BTreeMap::<(u32, String), Vec<Member>>::new();
+    for member in members {
+        groups.entry((member.pgid, member.user.clone())).or_default().push(member);
+    }
+
+    let mut jobs = groups
+        .into_iter()
+        .map(|((pgid, user), members)| {
+            let root = members
+                .iter()
+                .find(|member| member.pid == pgid)
+                .or_else(|| members.iter().min_by_key(|member| (member.start_time, member.pid)))
+                .expect("a group has at least one member");
+            Job {
+                pgid,
+                name: root.name.clone(),
+                user,
+                command: root.command.clone(),
+                usage: members.iter().map(|member| member.usage).sum(),
+                rss: members.iter().map(|member| member.rss).sum(),
+                processes: members.len() as u32,
+                start_time: members
+                    .iter()
+                    .map(|member| member.start_time)
+                    .filter(|&start_time| start_time > 0)
+                    .min(),
+            }
+        })
+        .filter(|job| {
+            job.usage >= thresholds.cpu || thresholds.mem.is_some_and(|mem| job.rss >= mem)
+        })
+        .collect::<Vec<_>>();
+
+    jobs.sort_by(|a, b| b.usage.total_cmp(&a.usage).then(b.rss.cmp(&a.rss)));
+    if let Some(max_processes) = thresholds.max_processes {
+        jobs.truncate(max_processes as usize);
+    }
+    jobs
+}
+
+/// Returns the process group id of the process with the given `pid`, unless it is gone.
+pub fn pgid(pid: sysinfo::Pid) -> Option<u32> {
+    // SAFETY: `getpgid` only reads its argument.
+    let pgid = unsafe { libc::getpgid(pid.as_u32() as libc::pid_t) };
+    u32::try_from(pgid).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(pid: u32, pgid: u32, user: &str, usage: f32) -> Member {
+        Member {
+            pid,
+            pgid,
+            user: user.to_string(),
+            name: format!("proc{pid}"),
+            command: Some(format!("proc{pid} --rank {pid}")),
+            usage,
+            rss: 1 << 20,
+            start_time: 1000 + pid as u64,
+        }
+    }
+
+    fn summary(jobs: &[Job]) -> Vec<(u32, &str, &str, f32, u32)> {
+        jobs.iter()
+            .map(|job| (job.pgid, job.user.as_str(), job.name.as_str(), job.usage, job.processes))
+            .collect()
+    }
+
+    #[test]
+    fn groups_processes_by_their_group_and_user() {
+        let members = vec![
+            member(100, 100, "marieke", 1.0),
+            member(101, 100, "marieke", 40.0),
+            member(102, 100, "marieke", 40.0),
+            // The same group may hold processes of another user, such as after `sudo`.
+            member(103, 100, "root", 30.0),
+            member(200, 200, "bob", 60.0),
+        ];
+        let jobs = jobs(members, &Thresholds::default());
+        assert_eq!(
+            summary(&jobs),
+            [
+                (100, "marieke", "proc100", 81.0, 3),
+                (200, "bob", "proc200", 60.0, 1),
+                (100, "root", "proc103", 30.0, 1),
+            ]
+        );
+        assert_eq!(jobs[0].command.as_deref(), Some("proc100 --rank 100"));
+        assert_eq!(jobs[0].rss, 3 << 20);
+        assert_eq!(jobs[0].start_time, Some(1100));
+    }
+
+    #[test]
+    fn stands_in_for_a_missing_leader() {
+        // The leader 300 is ignored or has exited, so the earliest started member names the job.
+        let mut late = member(301, 300, "marieke", 50.0);
+        late.start_time = 5000;
+        let jobs = jobs(
+            vec![late, member(303, 300, "marieke", 20.0), member(302, 300, "marieke", 20.0)],
+            &Thresholds::default(),
+        );
+        assert_eq!(summary(&jobs), [(300, "marieke", "proc302", 90.0, 3)]);
+        assert_eq!(jobs[0].start_time, Some(1302));
+    }
+
+    #[test]
+    fn counts_members_across_the_window() {
+        // Workers that each ran for part of the window, such as short compiler runs, are below the
+        // threshold by themselves but not together. Exited members no longer use any memory.
+        let mut members = (401..=410).map(|pid| member(pid, 400, "bob", 3.0)).collect::<Vec<_>>();
+        for member in &mut members[..5] {
+            member.rss = 0;
+        }
+        let jobs = jobs(members, &Thresholds::default());
+        assert_eq!(summary(&jobs), [(400, "bob", "proc401", 30.0, 10)]);
+        assert_eq!(jobs[0].rss, 5 << 20);
+    }
+
+    #[test]
+    fn selects_jobs_by_the_thresholds() {
+        let members = vec![
+            member(100, 100, "a", 5.0),
+            member(200, 200, "b", 50.0),
+            member(300, 300, "c", 20.0),
+            member(400, 400, "d", 30.0),
+        ];
+        let thresholds = Thresholds { max_processes: Some(2), ..Thresholds::default() };
+        let pgids =
+            jobs(members.clone(), &thresholds).iter().map(|job| job.pgid).collect::<Vec<_>>();
+        assert_eq!(pgids, [200, 400]);
+
+        let thresholds = Thresholds { mem: Some(1 << 20), ..Thresholds::default() };
+        assert_eq!(jobs(members, &thresholds).len(), 4);
+    }
+}
@@ -1,6 +1,7 @@
 use std::str::FromStr;
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use clap::Parser;
 use sysinfo::System;
 
 use mu::model::Usage;
@@ -8,13 +9,55 @@ use mu::model::Usage;
 use crate::config::Config;
 use crate::model::Gather;
 
+mod command;
 mod config;
+mod gpu;
+mod inventory;
+mod io;
+mod jobs;
 mod model; // TODO: Name?
+mod mounts;
+mod pressure;
+mod sessions;
+mod thermal;
+mod window;
 
 const DEFAULT_CONFIG_PATH: &str = "/martini/sshuser/mu/ignore.linus";
 
+/// Gather the usage information of this machine, and write it to stdout for the hive.
+#[derive(Debug, clap::Parser)]
+#[command(version)]
+struct Args {
+    /// Path to the configuration file. If it does not exist, nothing is ignored or renamed.
+    #[clap(default_value = DEFAULT_CONFIG_PATH)]
+    config: String,
+    /// Seconds over which the CPU usage is sampled.
+    ///
+    /// The mean and peak usage over this window are reported. The default is the shortest
+    /// interval over which the usage can be measured.
+    #[clap(long, default_value_t = sysinfo::MINIMUM_CPU_UPDATE_INTERVAL.as_secs_f32())]
+    window: f32,
+    /// Number of samples taken over the window.
+    #[clap(long, default_value_t = 1)]
+    samples: u32,
+    /// Also report the hardware inventory of the machine, which the hive asks for now and then.
+    #[clap(long)]
+    inventory: bool,
+}
+
 fn main() -> Result<()> {
-    let config_path = std::env::args().skip(1).next().unwrap_or(DEFAULT_CONFIG_PATH.to_string());
+    let args = Args::parse();
+    let window = match std::time::Duration::try_from_secs_f32(args.window) {
+        Ok(window) if !window.is_zero() => window,
+        _ => {
+            bail!("expected a positive number of seconds for the window, but found {}", args.window)
+        }
+    };
+    if args.samples == 0 {
+        bail!("expected at least one sample");
+    }
+
+    let config_path = args.config;
     let config = match std::fs::read_to_string(&config_path) {
         Ok(s) => Some(
             Config::from_str(&s).context(format!("could not parse config file {config_path:?}"))?,
@@ -35,16 +78,19 @@ fn main() -> Result<()> {
     );
 
     // We need to wait until we have enough cpu sampling.
-    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
-    system.refresh_all(); // TODO: Consider being more surgical in what we update at this point.
+    let sampled = window::sample(&mut system, window, args.samples);
+    system.refresh_memory();
+    // The frequency at the end of the window tells how fast the cores run under the sampled load.
+    system.refresh_cpu_frequency();
 
     // Read the system state.
-    let usage = Usage::gather(&system, config);
+    let mut usage = Usage::gather(&system, &sampled, config);
+    if args.inventory {
+        usage.inventory = Some(inventory::gather(&system));
+    }
 
-    // Send the serialized system usage info over stdout.
+    // Send the header and the serialized system usage info over stdout.
     let stdout = std::io::stdout().lock();
-    // TODO: Consider writing out some sort of version information first.
-    // TODO: Remove the pretty printing once this works.
-    serde_json::to_writer_pretty(stdout, &usage)?;
+    mu::protocol::write(stdout, &usage).context("could not write usage to stdout")?;
     Ok(())
 }
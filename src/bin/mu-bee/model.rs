@@ -1,14 +1,17 @@
-use mu::model::{Memory, PROCESS_USAGE_THRESHOLD_PERCENT, Process, Processes, Usage};
+use std::collections::BTreeMap;
+
+use mu::model::{Memory, Process, ProcessIo, Processes, Sampling, Thresholds, Usage};
 
 use crate::config::Config;
+use crate::window::Sampled;
 
 // TODO: Consider name space polution with `gather` function in mu-hive.
 pub trait Gather {
-    fn gather(system: &sysinfo::System, config: Config) -> Self;
+    fn gather(system: &sysinfo::System, sampled: &Sampled, config: Config) -> Self;
 }
 
 impl Gather for Usage {
-    fn gather(system: &sysinfo::System, config: Config) -> Self {
+    fn gather(system: &sysinfo::System, sampled: &Sampled, config: Config) -> Self {
         // TODO: Consider if this value is meaningfully different here than if we request it
         // _right_ after initializing the System, when the load average has been minimally poisoned
         // by our presence.
@@ -16,7 +19,10 @@ impl Gather for Usage {
         // Request the load average of the system before doing much processing ourselves.
         let load_avg = sysinfo::System::load_average().into();
 
+        let thresholds = config.thresholds;
         let mut procs = Vec::new();
+        // Processes below the thresholds, by user and name. See `combine`.
+        let mut small = BTreeMap::<(String, String), Vec<Process>>::new();
         let users = sysinfo::Users::new_with_refreshed_list();
         for proc in system.processes().values() {
             // Ignore the process of this program.
@@ -24,37 +30,375 @@ impl Gather for Usage {
                 continue;
             }
 
-            let mut name = proc.name().to_string_lossy().to_string();
-            let user = proc
-                .effective_user_id()
-                .or(proc.user_id())
+            let (mut name, comm) = process_names(proc);
+            let uid = proc.effective_user_id().or(proc.user_id());
+            let user = uid
                 .and_then(|uid| users.get_user_by_id(uid))
                 .map(|u| u.name())
                 .unwrap_or("?")
                 .to_string();
-            let cpu_usage = proc.cpu_usage();
+            let cpu = sampled.process(proc);
+            let cpu_usage = cpu.mean;
 
-            // Ignore processes based on their name, user, or due to low usage values.
-            let ignore = config.is_ignored_user(&user) || config.is_ignored_process(&name);
-            let low_usage = cpu_usage < PROCESS_USAGE_THRESHOLD_PERCENT;
-            if ignore || low_usage {
+            // Ignore processes based on their name, user, or due to low usage values. The threads
+            // of a process share its memory and I/O counters, so only the process itself can be large
+            // or busy with I/O.
+            let ignore = config.is_ignored_user(&user, uid.map(|uid| **uid))
+                || config.is_ignored_process(&name, &comm);
+            let is_thread = proc.thread_kind().is_some();
+            let large = !is_thread && thresholds.mem.is_some_and(|mem| proc.memory() >= mem);
+            let io = sampled.process_io(proc);
+            let busy_io =
+                !is_thread && thresholds.io.is_some_and(|min| io.read + io.written >= min);
+            let low_usage = cpu_usage < thresholds.cpu && !large && !busy_io;
+            // The usage of a thread is already part of the usage of its process, so only processes
+            // are set aside to be combined.
+            let idle = cpu_usage <= 0.0 || is_thread;
+            if ignore || (low_usage && idle) {
                 continue;
             }
 
             // Rename if necessary.
-            if let Some(renamed) = config.get_canonical_name(&name) {
-                name = renamed.to_string();
+            if let Some(renamed) = config.get_canonical_name(&name, &comm) {
+                name = renamed;
             }
 
-            procs.push(Process::new(name.clone(), user.clone(), cpu_usage));
+            let process = Process {
+                name: name.clone(),
+                user: user.clone(),
+                usage: cpu_usage,
+                peak_usage: Some(cpu.peak),
+                pid: proc.pid().as_u32(),
+                rss: proc.memory(),
+                virtual_memory: proc.virtual_memory(),
+                // The tasks of a process do not include its main thread.
+                threads: proc.tasks().map(|tasks| tasks.len() as u32 + 1),
+                start_time: Some(proc.start_time()).filter(|&start_time| start_time > 0),
+                command: sanitize_command(proc.cmd()),
+                combined: None,
+                io: crate::io::is_readable(proc.pid()).then_some(io),
+            };
+            if low_usage {
+                small.entry((user, name)).or_default().push(process);
+            } else {
+                procs.push(process);
+            }
         }
 
+        let procs = select(procs, small, &thresholds);
+
         Self {
-            global_cpu_usage: system.global_cpu_usage(),
-            cpus: system.cpus().iter().map(|cpu| cpu.cpu_usage()).collect(),
+            global_cpu_usage: sampled.global.mean,
+            cpus: sampled.cpus.iter().map(|cpu| cpu.mean).collect(),
+            cpus_peak: sampled.cpus.iter().map(|cpu| cpu.peak).collect(),
+            sampling: Some(Sampling {
+                window: sampled.window.as_secs_f32(),
+                samples: sampled.samples,
+            }),
             load_avg,
-            mem: Memory { total: system.total_memory(), used: system.used_memory() },
+            mem: Memory {
+                total: system.total_memory(),
+                used: system.used_memory(),
+                available: Some(system.available_memory()),
+            },
+            swap: Some(Memory {
+                total: system.total_swap(),
+                used: system.used_swap(),
+                available: None,
+            }),
+            pressure: crate::pressure::gather(),
             processes: Processes::new(procs.into_boxed_slice()),
+            mounts: crate::mounts::gather(&config),
+            gpus: crate::gpu::gather(system, &users, &config),
+            thresholds,
+            jobs: crate::jobs::gather(system, sampled, &users, &config),
+            networks: sampled.networks.clone(),
+            disks: sampled.disks.clone(),
+            thermal: crate::thermal::gather(system, sampled),
+            sessions: crate::sessions::gather(&users, &config),
+            boot_time: Some(sysinfo::System::boot_time()),
+            inventory: None,
+        }
+    }
+}
+
+/// Selects the processes to report from the ones that cross the `thresholds` by themselves, and
+/// the `small` ones below them by user and name.
+///
+/// Many small processes of the same kind add up, such as the workers of a parallel job. Those that
+/// cross the CPU threshold together are reported as a single combined entry. With a limit on the
+/// number of processes, the ones using the most CPU are kept.
+fn select(
+    mut procs: Vec<Process>,
+    small: BTreeMap<(String, String), Vec<Process>>,
+    thresholds: &Thresholds,
+) -> Vec<Process> {
+    procs.extend(
+        small
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .map(combine)
+            .filter(|combined| combined.usage >= thresholds.cpu),
+    );
+
+    if let Some(max_processes) = thresholds.max_processes {
+        procs.sort_by(|a, b| b.usage.total_cmp(&a.usage).then(b.rss.cmp(&a.rss)));
+        procs.truncate(max_processes as usize);
+    }
+    procs
+}
+
+/// Combines processes of the same user and name into a single entry.
+///
+/// Their usage, memory, threads, and throughput are summed, and the start time is that of the
+/// earliest one. Since the entry does not stand for a single process, it has no pid or command
+/// line.
+fn combine(processes: Vec<Process>) -> Process {
+    let first = &processes[0];
+    Process {
+        name: first.name.clone(),
+        user: first.user.clone(),
+        usage: processes.iter().map(|proc| proc.usage).sum(),
+        // The peaks of the processes need not coincide, so their sum would overstate the peak.
+        peak_usage: None,
+        pid: 0,
+        rss: processes.iter().map(|proc| proc.rss).sum(),
+        virtual_memory: processes.iter().map(|proc| proc.virtual_memory).sum(),
+        threads: processes.iter().map(|proc| proc.threads).sum(),
+        start_time: processes.iter().filter_map(|proc| proc.start_time).min(),
+        command: None,
+        combined: Some(processes.len() as u32),
+        io: processes
+            .iter()
+            .filter_map(|proc| proc.io)
+            .reduce(|a, b| ProcessIo { read: a.read + b.read, written: a.written + b.written }),
+    }
+}
+
+/// Length of the comm name of a process that has been truncated by the kernel, in bytes.
+const COMM_LENGTH: usize = 15;
+
+/// Returns the full name and the comm name of a process, in that order.
+///
+/// The kernel keeps the name of a process in its comm field, which is truncated to 15 bytes. This
+/// turns `gsd-housekeeping` into `gsd-housekeepin`. A truncated name is completed from the
+/// basename of `argv[0]` or of the executable, provided that it starts with the comm name. A
+/// process that renamed itself, such as a Firefox content process called `Isolated Web Co`, keeps
+/// its comm name.
+pub fn process_names(proc: &sysinfo::Process) -> (String, String) {
+    let comm = proc.name().to_string_lossy().to_string();
+    if comm.len() < COMM_LENGTH {
+        return (comm.clone(), comm);
+    }
+    let basename =
+        |path: &std::path::Path| path.file_name().map(|name| name.to_string_lossy().to_string());
+    let argv0 = proc.cmd().first().and_then(|arg| basename(std::path::Path::new(arg)));
+    let exe = proc.exe().and_then(basename);
+    let name = [argv0, exe]
+        .into_iter()
+        .flatten()
+        .find(|name| name.starts_with(&comm))
+        .unwrap_or_else(|| comm.clone());
+    (name, comm)
+}
+
+/// Longest command line that is reported, in characters.
+const MAX_COMMAND_LENGTH: usize = 200;
+
+/// Words in the name of an option or variable which tell that its value should not be shown to
+/// everybody using the viewer, as in `--api-key` or `DB_PASSWORD`.
+const SECRET_WORDS: &[&str] = &[
+    "password",
+    "passwd",
+    "passphrase",
+    "pwd",
+    "token",
+    "secret",
+    "key",
+    "apikey",
+    "auth",
+    "credential",
+    "credentials",
+];
+
+/// Returns whether the name of an option or variable looks like it holds a secret.
+///
+/// The name is split into words at anything but letters and digits, so `--api-key` and
+/// `AWS_SECRET_ACCESS_KEY` are secret while `--keyboard` and `--author` are not. Longer words also
+/// count at the end of a word, as in `--accesstoken`.
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.split(|c: char| !c.is_ascii_alphanumeric()).any(|word| {
+        SECRET_WORDS
+            .iter()
+            .any(|secret| word == *secret || (secret.len() > 4 && word.ends_with(secret)))
+    })
+}
+
+/// Joins the arguments of a command line into a single line that is safe to show to others.
+///
+/// Values of options and variables whose names look like they hold a secret, such as
+/// `--password=hunter2`, `--token hunter2`, or `API_KEY=hunter2`, are redacted. Other arguments,
+/// such as file names, are left alone. Control characters are replaced, and the line is truncated
+/// to [`MAX_COMMAND_LENGTH`] characters. Returns [`None`] for an empty command line, as is the
+/// case for kernel threads.
+pub fn sanitize_command(cmd: &[std::ffi::OsString]) -> Option<String> {
+    // Only a variable name of a single word can be assigned to, unlike `x=1,y=2.csv`.
+    let is_variable = |s: &str| {
+        !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    };
+
+    let mut args = Vec::with_capacity(cmd.len());
+    let mut redact_next = false;
+    for arg in cmd {
+        let arg = arg.to_string_lossy();
+        if redact_next && !arg.starts_with('-') {
+            args.push("***".to_string());
+            redact_next = false;
+            continue;
+        }
+        redact_next = false;
+        let option = arg.starts_with('-');
+        match arg.split_once('=') {
+            Some((name, _value)) if is_secret_name(name) && (option || is_variable(name)) => {
+                args.push(format!("{name}=***"))
+            }
+            Some(_) => args.push(arg.to_string()),
+            None => {
+                redact_next = option && is_secret_name(&arg);
+                args.push(arg.to_string());
+            }
         }
     }
+
+    let line = args.join(" ");
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    let mut sanitized = line
+        .chars()
+        .map(|c| if c.is_control() { '?' } else { c })
+        .take(MAX_COMMAND_LENGTH)
+        .collect::<String>();
+    if line.chars().count() > MAX_COMMAND_LENGTH {
+        sanitized.push('…');
+    }
+    Some(sanitized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(user: &str, name: &str, usage: f32, rss: u64) -> Process {
+        Process {
+            name: name.to_string(),
+            user: user.to_string(),
+            usage,
+            peak_usage: Some(usage),
+            pid: 1000 + rss as u32,
+            rss,
+            threads: Some(2),
+            start_time: Some(100 + rss),
+            command: Some(name.to_string()),
+            io: Some(ProcessIo { read: rss, written: 1 }),
+            ..Default::default()
+        }
+    }
+
+    fn group(processes: &[Process]) -> ((String, String), Vec<Process>) {
+        ((processes[0].user.clone(), processes[0].name.clone()), processes.to_vec())
+    }
+
+    #[test]
+    fn combines_small_processes_of_the_same_kind() {
+        let workers = (1..=4).map(|n| process("alice", "python", 8.0, n)).collect::<Vec<_>>();
+        let small = BTreeMap::from([
+            group(&workers),
+            // A single small process has nothing to add up with.
+            group(&[process("bob", "make", 9.0, 10)]),
+            // Nor do a few that stay below the threshold together.
+            group(&[process("carol", "cc1", 3.0, 11), process("carol", "cc1", 3.0, 12)]),
+        ]);
+        let large = vec![process("dave", "blender", 50.0, 20), process("erin", "redis", 0.5, 30)];
+        let thresholds = Thresholds::default();
+
+        let selected = select(large, small, &thresholds);
+        let summary = selected
+            .iter()
+            .map(|proc| (proc.user.as_str(), proc.name.as_str(), proc.combined))
+            .collect::<Vec<_>>();
+        // The large processes are kept even when they are idle.
+        assert_eq!(
+            summary,
+            [("dave", "blender", None), ("erin", "redis", None), ("alice", "python", Some(4))]
+        );
+
+        let combined = &selected[2];
+        assert_eq!(combined.usage, 32.0);
+        assert_eq!(combined.peak_usage, None);
+        assert_eq!((combined.pid, combined.command.as_deref()), (0, None));
+        assert_eq!(combined.rss, 10);
+        assert_eq!(combined.threads, Some(8));
+        assert_eq!(combined.start_time, Some(101));
+        let io = combined.io.unwrap();
+        assert_eq!((io.read, io.written), (10, 4));
+    }
+
+    #[test]
+    fn keeps_the_busiest_processes() {
+        let workers = (1..=4).map(|n| process("alice", "python", 8.0, n)).collect::<Vec<_>>();
+        let procs = vec![
+            process("erin", "redis", 0.5, 30),
+            process("dave", "blender", 50.0, 20),
+            process("frank", "julia", 12.0, 40),
+            process("grace", "julia", 12.0, 50),
+        ];
+        let thresholds = Thresholds { max_processes: Some(3), ..Thresholds::default() };
+
+        let selected = select(procs, BTreeMap::from([group(&workers)]), &thresholds);
+        let summary =
+            selected.iter().map(|proc| (proc.user.as_str(), proc.usage)).collect::<Vec<_>>();
+        // Equal usage is settled by memory.
+        assert_eq!(summary, [("dave", 50.0), ("alice", 32.0), ("grace", 12.0)]);
+    }
+
+    fn sanitize(args: &[&str]) -> String {
+        let cmd = args.iter().map(std::ffi::OsString::from).collect::<Vec<_>>();
+        sanitize_command(&cmd).unwrap_or_default()
+    }
+
+    #[test]
+    fn redacts_secret_options() {
+        assert_eq!(sanitize(&["mysql", "--password=hunter2"]), "mysql --password=***");
+        assert_eq!(sanitize(&["gh", "--token", "hunter2", "-v"]), "gh --token *** -v");
+        assert_eq!(sanitize(&["cli", "--api-key", "hunter2"]), "cli --api-key ***");
+        assert_eq!(sanitize(&["cli", "--accesstoken=hunter2"]), "cli --accesstoken=***");
+        assert_eq!(sanitize(&["env", "API_KEY=hunter2", "run"]), "env API_KEY=*** run");
+        assert_eq!(
+            sanitize(&["env", "AWS_SECRET_ACCESS_KEY=hunter2"]),
+            "env AWS_SECRET_ACCESS_KEY=***"
+        );
+    }
+
+    #[test]
+    fn keeps_harmless_arguments() {
+        let args = ["python", "monkey.py", "--keyboard", "us", "--author", "jan", "keyframes.mp4"];
+        assert_eq!(sanitize(&args), args.join(" "));
+        assert_eq!(
+            sanitize(&["plot", "--author=jan", "x=1,key=2.csv"]),
+            "plot --author=jan x=1,key=2.csv"
+        );
+        // A secret option followed by another option has no value to redact.
+        assert_eq!(sanitize(&["gh", "--token", "--verbose"]), "gh --token --verbose");
+    }
+
+    #[test]
+    fn cleans_up_the_line() {
+        assert_eq!(sanitize_command(&[]), None);
+        assert_eq!(sanitize(&["echo", "a\tb"]), "echo a?b");
+        let long = "x".repeat(MAX_COMMAND_LENGTH + 10);
+        assert_eq!(sanitize(&[&long]).chars().count(), MAX_COMMAND_LENGTH + 1);
+    }
 }
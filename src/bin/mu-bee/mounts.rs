@@ -0,0 +1,291 @@
+//! Usage of the mounted file systems.
+
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use mu::model::{Capacity, Mount, MountHealth};
+
+use crate::config::Config;
+
+/// File system types that live on another machine.
+const REMOTE_FS_TYPES: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "ceph",
+    "glusterfs",
+    "lustre",
+    "beegfs",
+    "gpfs",
+    "afs",
+    "fuse.sshfs",
+];
+
+/// An entry of the mount table.
+#[derive(Debug, Clone)]
+pub struct MountEntry {
+    pub path: String,
+    pub fs_type: String,
+}
+
+impl MountEntry {
+    pub fn is_remote(&self) -> bool {
+        REMOTE_FS_TYPES.contains(&self.fs_type.as_str())
+    }
+}
+
+/// Reads the mount table from `/proc/mounts`.
+///
+/// If a path is mounted over multiple times, only the topmost mount is returned, since that is the
+/// one that is visible.
+pub fn read_mount_table() -> std::io::Result<Vec<MountEntry>> {
+    let table = std::fs::read_to_string("/proc/mounts")?;
+    let mut entries = Vec::<MountEntry>::new();
+    for line in table.lines() {
+        // Each line reads `device path type options dump pass`.
+        let mut fields = line.split_whitespace();
+        let (Some(_device), Some(path), Some(fs_type)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let path = unescape(path);
+        entries.retain(|entry| entry.path != path);
+        entries.push(MountEntry { path, fs_type: fs_type.to_string() });
+    }
+    Ok(entries)
+}
+
+/// Gathers the usage of the mounts selected by the `config`.
+///
+/// Each mount is probed in its own thread, such that a hung file system cannot hold up the bee. A
+/// mount that does not respond before the timeout is reported as hung, and its probe is abandoned.
+/// Network file systems wait for their servers killably, so the probe is cleaned up when the bee
+/// exits.
+pub fn gather(config: &Config) -> Box<[Mount]> {
+    let entries = match read_mount_table() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("WARNING: Could not read the mount table: {e}");
+            return Box::default();
+        }
+    };
+
+    let (slow, timeout) = config.mount_probe_limits();
+    let probes = entries
+        .into_iter()
+        .filter(|entry| config.is_selected_mount(&entry.path, &entry.fs_type))
+        .map(|entry| (probe(&entry.path), entry))
+        .collect::<Vec<_>>();
+    collect(probes, slow, timeout)
+}
+
+/// Waits for the `probes` of the mounts until `timeout` has passed, and reports the mounts that
+/// took longer than `slow` as slow, and the ones that have not answered as hung.
+fn collect(
+    probes: Vec<(mpsc::Receiver<ProbeResult>, MountEntry)>,
+    slow: Duration,
+    timeout: Duration,
+) -> Box<[Mount]> {
+    let deadline = Instant::now() + timeout;
+    let mut mounts = Vec::new();
+    for (probe, entry) in probes {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match probe.recv_timeout(remaining) {
+            Ok((Ok(stat), elapsed)) => {
+                let health = if elapsed > slow { MountHealth::Slow } else { MountHealth::Healthy };
+                if !health.is_healthy() {
+                    eprintln!("WARNING: Mount {:?} took {elapsed:?} to respond.", entry.path);
+                }
+                mounts.push(into_mount(&entry, Some(&stat), health));
+            }
+            Ok((Err(e), _)) => eprintln!("WARNING: Could not stat mount {:?}: {e}", entry.path),
+            Err(_) => {
+                eprintln!("WARNING: Mount {:?} did not respond within {timeout:?}.", entry.path);
+                mounts.push(into_mount(&entry, None, MountHealth::Hung));
+            }
+        }
+    }
+    mounts.into_boxed_slice()
+}
+
+type ProbeResult = (std::io::Result<libc::statvfs>, Duration);
+
+/// Starts a thread that stats the file system at `path`, and reports how long that took.
+fn probe(path: &str) -> mpsc::Receiver<ProbeResult> {
+    let (sender, receiver) = mpsc::channel();
+    let path = path.to_string();
+    std::thread::spawn(move || {
+        let start = Instant::now();
+        let stat = statvfs(&path);
+        // Nobody may be listening anymore if we took too long, and that is fine.
+        let _ = sender.send((stat, start.elapsed()));
+    });
+    receiver
+}
+
+pub fn into_mount(entry: &MountEntry, stat: Option<&libc::statvfs>, health: MountHealth) -> Mount {
+    Mount {
+        path: entry.path.clone(),
+        fs_type: entry.fs_type.clone(),
+        remote: entry.is_remote(),
+        health,
+        space: stat.map(space).unwrap_or_default(),
+        inodes: stat.and_then(inodes),
+    }
+}
+
+fn space(stat: &libc::statvfs) -> Capacity {
+    // The block counts are in units of the fragment size.
+    let fragment = if stat.f_frsize > 0 { stat.f_frsize } else { stat.f_bsize };
+    Capacity {
+        total: stat.f_blocks.saturating_mul(fragment),
+        // Some file systems, such as those of FUSE, can report more free blocks than they have.
+        used: stat.f_blocks.saturating_sub(stat.f_bfree).saturating_mul(fragment),
+        available: stat.f_bavail.saturating_mul(fragment),
+    }
+}
+
+fn inodes(stat: &libc::statvfs) -> Option<Capacity> {
+    // File systems that allocate inodes dynamically, such as btrfs, report zero of them.
+    (stat.f_files > 0).then(|| Capacity {
+        total: stat.f_files,
+        used: stat.f_files.saturating_sub(stat.f_ffree),
+        available: stat.f_favail,
+    })
+}
+
+pub fn statvfs(path: &str) -> std::io::Result<libc::statvfs> {
+    let c_path = std::ffi::CString::new(path)?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: The path is a valid nul-terminated string, and `stat` points to enough space for
+    // the result.
+    let ret = unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: A successful call has initialized `stat`.
+    Ok(unsafe { stat.assume_init() })
+}
+
+/// Undoes the octal escapes of whitespace and backslashes in `/proc/mounts`, such as `\040`.
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some((before, after)) = rest.split_once('\\') {
+        unescaped.push_str(before);
+        let code = after
+            .get(..3)
+            .filter(|digits| digits.bytes().all(|b| matches!(b, b'0'..=b'7')))
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match code {
+            Some(code) => {
+                unescaped.push(code as char);
+                rest = &after[3..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = after;
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescapes_mount_paths() {
+        assert_eq!(unescape("/mnt/plain"), "/mnt/plain");
+        assert_eq!(unescape("/mnt/My\\040Drive"), "/mnt/My Drive");
+        assert_eq!(unescape("/mnt/tab\\011and\\134slash"), "/mnt/tab\tand\\slash");
+        assert_eq!(unescape("\\040\\040"), "  ");
+        // Anything that is not a three-digit octal escape is left alone.
+        assert_eq!(unescape("/mnt/odd\\"), "/mnt/odd\\");
+        assert_eq!(unescape("/mnt/odd\\04"), "/mnt/odd\\04");
+        assert_eq!(unescape("/mnt/odd\\089"), "/mnt/odd\\089");
+        assert_eq!(unescape("/mnt/odd\\+12"), "/mnt/odd\\+12");
+        assert_eq!(unescape("/mnt/odd\\777"), "/mnt/odd\\777");
+    }
+
+    fn fake_statvfs(blocks: u64, bfree: u64, bavail: u64, files: u64, ffree: u64) -> libc::statvfs {
+        // SAFETY: The struct consists of plain integers, for which zero is a valid value.
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        stat.f_bsize = 4096;
+        stat.f_frsize = 1024;
+        (stat.f_blocks, stat.f_bfree, stat.f_bavail) = (blocks, bfree, bavail);
+        (stat.f_files, stat.f_ffree, stat.f_favail) = (files, ffree, ffree);
+        stat
+    }
+
+    #[test]
+    fn computes_space_and_inodes() {
+        let stat = fake_statvfs(1000, 400, 350, 100, 60);
+        let Capacity { total, used, available } = space(&stat);
+        assert_eq!((total, used, available), (1_024_000, 614_400, 358_400));
+        let inodes = inodes(&stat).unwrap();
+        assert_eq!((inodes.total, inodes.used, inodes.available), (100, 40, 60));
+
+        // Without a fragment size, the block size is the unit.
+        let mut stat = stat;
+        stat.f_frsize = 0;
+        assert_eq!(space(&stat).total, 4_096_000);
+    }
+
+    #[test]
+    fn copes_with_odd_file_system_statistics() {
+        // More free than there is at all.
+        let stat = fake_statvfs(100, 200, 200, 10, 20);
+        assert_eq!(space(&stat).used, 0);
+        assert_eq!(inodes(&stat).unwrap().used, 0);
+        // No inodes at all.
+        assert!(inodes(&fake_statvfs(100, 50, 50, 0, 0)).is_none());
+        assert_eq!(space(&fake_statvfs(u64::MAX, 0, 0, 0, 0)).total, u64::MAX);
+    }
+
+    #[test]
+    fn reports_slow_and_hung_mounts() {
+        let entry = |path: &str| MountEntry { path: path.to_string(), fs_type: "nfs4".to_string() };
+        let answered = |result: ProbeResult| {
+            let (sender, receiver) = mpsc::channel();
+            sender.send(result).unwrap();
+            receiver
+        };
+        let (_hanging, hung) = mpsc::channel();
+        let probes = vec![
+            (
+                answered((Ok(fake_statvfs(100, 50, 50, 10, 5)), Duration::from_millis(1))),
+                entry("/fast"),
+            ),
+            (
+                answered((Ok(fake_statvfs(100, 50, 50, 10, 5)), Duration::from_secs(1))),
+                entry("/slow"),
+            ),
+            (answered((Err(std::io::Error::other("gone")), Duration::ZERO)), entry("/gone")),
+            (hung, entry("/hung")),
+        ];
+        let start = Instant::now();
+        let mounts = collect(probes, Duration::from_millis(500), Duration::from_millis(50));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        let summary = mounts
+            .iter()
+            .map(|mount| (mount.path.as_str(), mount.health, mount.known_space().is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("/fast", MountHealth::Healthy, true),
+                ("/slow", MountHealth::Slow, true),
+                ("/hung", MountHealth::Hung, false),
+            ]
+        );
+        assert!(mounts.iter().all(|mount| mount.remote));
+        assert_eq!(mounts[2].space.total, 0);
+    }
+}
@@ -0,0 +1,108 @@
+//! Pressure stall information, from `/proc/pressure`.
+//!
+//! Each file holds a `some` line and, for memory and IO, a `full` line:
+//!
+//! ```text
+//! some avg10=0.00 avg60=0.00 avg300=0.00 total=0
+//! full avg10=0.00 avg60=0.00 avg300=0.00 total=0
+//! ```
+//!
+//! Kernels without `CONFIG_PSI`, or that have it disabled with `psi=0`, lack these files. Newer
+//! kernels also write a `full` line for the CPU, but it is always zero at the system level, so we
+//! leave it out.
+
+use mu::model::{Pressure, Stall, StallAverages};
+
+/// Reads the pressure on the CPU, memory, and IO, where the kernel provides it.
+pub fn gather() -> Pressure {
+    Pressure {
+        cpu: read("cpu").map(|stall| Stall { full: None, ..stall }),
+        memory: read("memory"),
+        io: read("io"),
+    }
+}
+
+fn read(resource: &str) -> Option<Stall> {
+    let path = format!("/proc/pressure/{resource}");
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            eprintln!("WARNING: Could not read {path:?}: {e}");
+            return None;
+        }
+    };
+    let stall = parse(&contents);
+    if stall.is_none() {
+        eprintln!("WARNING: Could not parse {path:?}.");
+    }
+    stall
+}
+
+/// Parses the contents of a pressure file. The `some` line is required.
+fn parse(s: &str) -> Option<Stall> {
+    let mut some = None;
+    let mut full = None;
+    for line in s.lines() {
+        let Some((kind, averages)) = line.split_once(' ') else { continue };
+        match kind {
+            "some" => some = Some(parse_averages(averages)?),
+            "full" => full = Some(parse_averages(averages)?),
+            _ => {}
+        }
+    }
+    Some(Stall { some: some?, full })
+}
+
+/// Parses `avg10=0.00 avg60=0.00 avg300=0.00 total=0`, ignoring the total.
+fn parse_averages(s: &str) -> Option<StallAverages> {
+    let mut averages = StallAverages { avg10: 0.0, avg60: 0.0, avg300: 0.0 };
+    for field in s.split_whitespace() {
+        let (key, value) = field.split_once('=')?;
+        let average = match key {
+            "avg10" => &mut averages.avg10,
+            "avg60" => &mut averages.avg60,
+            "avg300" => &mut averages.avg300,
+            _ => continue,
+        };
+        *average = value.parse().ok()?;
+    }
+    Some(averages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_some_and_full() {
+        let stall = parse(
+            "some avg10=1.50 avg60=0.75 avg300=0.10 total=12345\n\
+             full avg10=0.50 avg60=0.25 avg300=0.00 total=6789\n",
+        )
+        .unwrap();
+        assert_eq!(stall.some.avg10, 1.5);
+        assert_eq!(stall.some.avg60, 0.75);
+        assert_eq!(stall.some.avg300, 0.1);
+        assert_eq!(stall.full.unwrap().avg10, 0.5);
+    }
+
+    #[test]
+    fn parses_the_cpu_file_without_full() {
+        let stall = parse("some avg10=3.20 avg60=1.00 avg300=0.40 total=999\n").unwrap();
+        assert_eq!(stall.some.avg10, 3.2);
+        assert!(stall.full.is_none());
+    }
+
+    #[test]
+    fn requires_some() {
+        assert!(parse("full avg10=0.00 avg60=0.00 avg300=0.00 total=0\n").is_none());
+        assert!(parse("").is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_averages() {
+        assert!(parse("some avg10=high avg60=0.00 avg300=0.00 total=0\n").is_none());
+        assert!(parse("some avg10 avg60=0.00 avg300=0.00 total=0\n").is_none());
+    }
+}
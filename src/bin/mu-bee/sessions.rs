@@ -0,0 +1,302 @@
+//! Login sessions, from systemd-logind or utmp.
+//!
+//! On machines with systemd, logind keeps a file for each session in `/run/systemd/sessions`,
+//! with lines such as `USER=marieke`, `TYPE=wayland`, `SEAT=seat0`, and `REMOTE=0`. This is the
+//! only record of graphical sessions on modern desktops, since neither GDM on Wayland nor the
+//! terminals within it write utmp anymore. Machines without logind are left with utmp, where the
+//! programs that log users in, such as `sshd`, `login`, and display managers, record each session
+//! along with its terminal and the host it came from.
+//!
+//! Like `w`, we take the time since the terminal of a session was last read from as its idle time,
+//! since reading from a terminal is what happens when its user types. A graphical session reads its
+//! input elsewhere. Its desktop tells logind when it goes idle instead, which we ask `loginctl`
+//! for. Without logind, the idle time of a display such as `:0` is unknown.
+
+use std::collections::HashMap;
+use std::os::unix::fs::MetadataExt;
+use std::time::Duration;
+
+use mu::model::{Session, SessionKind, unix_timestamp};
+
+use crate::config::Config;
+
+/// Directory in which logind keeps a file for each session.
+const LOGIND_SESSIONS: &str = "/run/systemd/sessions";
+
+/// Time `loginctl` may take before we give up on the idle time of a graphical session.
+const LOGINCTL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Types of logind sessions that take place on a display.
+const GRAPHICAL_TYPES: &[&str] = &["x11", "wayland", "mir"];
+
+/// Gathers the sessions of the users that are logged in, except for the ignored users.
+///
+/// The sessions are read from logind if it lists any, and from utmp otherwise.
+pub fn gather(users: &sysinfo::Users, config: &Config) -> Box<[Session]> {
+    let now = unix_timestamp();
+    let mut sessions = from_logind(now);
+    if sessions.is_empty() {
+        sessions = from_utmp(now)
+            .into_iter()
+            .map(|session| {
+                let user = users.iter().find(|user| user.name() == session.user);
+                let uid = user.map(|user| **user.id());
+                (session, uid)
+            })
+            .collect();
+    }
+
+    let mut sessions = sessions
+        .into_iter()
+        .filter(|(session, uid)| !config.is_ignored_user(&session.user, *uid))
+        .map(|(session, _)| session)
+        .collect::<Vec<_>>();
+    sessions.sort_by(|a, b| a.user.cmp(&b.user).then(a.login_time.cmp(&b.login_time)));
+    sessions.into_boxed_slice()
+}
+
+/// Reads the sessions of users from logind, along with their user ids.
+fn from_logind(now: u64) -> Vec<(Session, Option<u32>)> {
+    let Ok(entries) = std::fs::read_dir(LOGIND_SESSIONS) else { return Vec::new() };
+    let mut sessions = Vec::new();
+    for entry in entries.flatten() {
+        // Next to the session files are FIFOs such as `2.ref`, which would block a reader.
+        if !entry.file_type().is_ok_and(|file_type| file_type.is_file()) {
+            continue;
+        }
+        let Ok(contents) = std::fs::read_to_string(entry.path()) else { continue };
+        let Some((mut session, uid)) = parse_logind(&contents, now) else { continue };
+        if session.idle.is_none() {
+            session.idle = match session.kind {
+                SessionKind::Graphical => {
+                    let id = entry.file_name().to_string_lossy().to_string();
+                    idle_hint(&id, now)
+                }
+                _ => idle(&session.line, now),
+            };
+        }
+        sessions.push((session, uid));
+    }
+    sessions
+}
+
+/// Parses a session file of logind, if it describes a session of a user that is still going on.
+///
+/// Sessions of other classes, such as those of the greeter of a display manager or of `systemd
+/// --user`, are left out. So are sessions whose user has logged out, but whose processes linger.
+fn parse_logind(s: &str, now: u64) -> Option<(Session, Option<u32>)> {
+    let fields = s
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .collect::<HashMap<_, _>>();
+    let field = |key: &str| fields.get(key).copied().filter(|value| !value.is_empty());
+    if field("CLASS") != Some("user") || field("STATE") == Some("closing") {
+        return None;
+    }
+
+    let kind = if field("REMOTE") == Some("1") {
+        SessionKind::Remote
+    } else if field("TYPE").is_some_and(|kind| GRAPHICAL_TYPES.contains(&kind)) {
+        SessionKind::Graphical
+    } else if field("SEAT").is_some() {
+        SessionKind::Console
+    } else {
+        SessionKind::Terminal
+    };
+    // The idle hint is kept by logind itself, and only makes it into the file on some versions.
+    let idle = match field("IDLE_HINT") {
+        Some("1") => field("IDLE_SINCE_HINT")
+            .and_then(|since| since.parse::<u64>().ok())
+            .map(|since| now.saturating_sub(since / 1_000_000)),
+        Some(_) => Some(0),
+        None => None,
+    };
+    let session = Session {
+        user: field("USER")?.to_string(),
+        line: field("TTY").or(field("DISPLAY")).or(field("SEAT")).unwrap_or("?").to_string(),
+        host: field("REMOTE_HOST").map(str::to_string),
+        kind,
+        // The login time is in microseconds.
+        login_time: field("REALTIME")?.parse::<u64>().ok()? / 1_000_000,
+        idle,
+    };
+    Some((session, field("UID").and_then(|uid| uid.parse().ok())))
+}
+
+/// Asks logind how long the session with the given `id` has been idle, as its desktop reported.
+///
+/// A session that is not idle has seen input within the idle delay of its desktop, which we take
+/// as no idle time at all.
+fn idle_hint(id: &str, now: u64) -> Option<u64> {
+    if !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let command =
+        format!("loginctl show-session {id} --property=IdleHint --property=IdleSinceHint");
+    let output = match crate::command::run_query(&command, LOGINCTL_TIMEOUT) {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("WARNING: Could not ask logind whether session {id} is idle: {e}");
+            return None;
+        }
+    };
+    let properties =
+        output.lines().filter_map(|line| line.split_once('=')).collect::<HashMap<_, _>>();
+    match properties.get("IdleHint").copied()? {
+        "yes" => {
+            let since = properties.get("IdleSinceHint")?.parse::<u64>().ok()?;
+            Some(now.saturating_sub(since / 1_000_000))
+        }
+        _ => Some(0),
+    }
+}
+
+/// Reads the sessions of users from utmp.
+///
+/// Entries of sessions whose process is gone are left out, since they are left behind when a
+/// login program does not get to clean up after itself.
+fn from_utmp(now: u64) -> Vec<Session> {
+    let mut sessions = Vec::new();
+    // SAFETY: The utmp functions share their state between calls, so they must not be used from
+    // several threads at once. Only this thread uses them. The entry returned by `getutxent` lives
+    // until the next call, and we copy what we need out of it before then.
+    unsafe {
+        libc::setutxent();
+        loop {
+            let entry = libc::getutxent();
+            if entry.is_null() {
+                break;
+            }
+            let entry = &*entry;
+            if entry.ut_type != libc::USER_PROCESS {
+                continue;
+            }
+            if !std::path::Path::new(&format!("/proc/{}", entry.ut_pid)).exists() {
+                continue;
+            }
+            let line = field(&entry.ut_line);
+            let host = Some(field(&entry.ut_host)).filter(|host| !host.is_empty());
+            sessions.push(Session {
+                user: field(&entry.ut_user),
+                kind: kind(&line, host.as_deref()),
+                idle: idle(&line, now),
+                line,
+                host,
+                login_time: u64::try_from(entry.ut_tv.tv_sec).unwrap_or_default(),
+            });
+        }
+        libc::endutxent();
+    }
+    sessions
+}
+
+/// Reads a fixed-size string field of a utmp entry, which is only terminated if it is shorter
+/// than the field.
+fn field(chars: &[libc::c_char]) -> String {
+    let bytes = chars.iter().map(|&c| c as u8).take_while(|&b| b != 0).collect::<Vec<_>>();
+    String::from_utf8_lossy(&bytes).trim().to_string()
+}
+
+/// Tells where a utmp session takes place from its terminal `line` and its `host`.
+///
+/// Remote sessions have the address of the other machine as their host. Terminal multiplexers
+/// such as tmux put their own description there, as in `tmux(1234).%0`. Graphical sessions and the
+/// terminals opened within them have a display such as `:0` instead.
+fn kind(line: &str, host: Option<&str>) -> SessionKind {
+    let display = host.is_some_and(|host| host.starts_with(':'));
+    match host {
+        _ if line.starts_with(':') => SessionKind::Graphical,
+        _ if display && !line.starts_with("pts/") => SessionKind::Graphical,
+        Some(host) if !display && !host.contains('(') => SessionKind::Remote,
+        _ if line.starts_with("tty") => SessionKind::Console,
+        // A terminal within a graphical session means that its user is at the display.
+        _ if display => SessionKind::Graphical,
+        _ => SessionKind::Terminal,
+    }
+}
+
+/// Returns the seconds since the terminal `line` was last read from, if it is a terminal.
+fn idle(line: &str, now: u64) -> Option<u64> {
+    if line.is_empty() || line.starts_with(':') {
+        return None;
+    }
+    let metadata = std::fs::metadata(format!("/dev/{line}")).ok()?;
+    let atime = u64::try_from(metadata.atime()).ok()?;
+    Some(now.saturating_sub(atime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_the_kind_of_utmp_sessions() {
+        assert_eq!(kind(":0", Some(":0")), SessionKind::Graphical);
+        assert_eq!(kind("tty2", None), SessionKind::Console);
+        assert_eq!(kind("pts/3", Some(":0")), SessionKind::Graphical);
+        assert_eq!(kind("pts/4", Some("10.0.0.5")), SessionKind::Remote);
+        assert_eq!(kind("pts/4", Some("laptop.example.org")), SessionKind::Remote);
+        assert_eq!(kind("pts/5", Some("tmux(123).%0")), SessionKind::Terminal);
+        assert_eq!(kind("pts/6", None), SessionKind::Terminal);
+    }
+
+    const WAYLAND: &str = "\
+# This is private data. Do not parse.
+UID=1000
+USER=marieke
+ACTIVE=1
+IS_DISPLAY=1
+STATE=active
+REMOTE=0
+TYPE=wayland
+CLASS=user
+SEAT=seat0
+TTY=tty2
+SERVICE=gdm-password
+DESKTOP=GNOME
+VTNR=2
+REALTIME=1792180000123456
+";
+
+    const SSH: &str = "\
+UID=1001
+USER=bob
+STATE=active
+REMOTE=1
+TYPE=tty
+CLASS=user
+TTY=pts/4
+REMOTE_HOST=10.0.0.5
+SERVICE=sshd
+REALTIME=1792190000000000
+IDLE_HINT=1
+IDLE_SINCE_HINT=1792190400000000
+";
+
+    #[test]
+    fn parses_logind_sessions() {
+        let (session, uid) = parse_logind(WAYLAND, 1792190600).unwrap();
+        assert_eq!(uid, Some(1000));
+        assert_eq!(session.user, "marieke");
+        assert_eq!(session.kind, SessionKind::Graphical);
+        assert_eq!(session.line, "tty2");
+        assert_eq!(session.host, None);
+        assert_eq!(session.login_time, 1792180000);
+        assert_eq!(session.idle, None);
+
+        let (session, _) = parse_logind(SSH, 1792190600).unwrap();
+        assert_eq!(session.kind, SessionKind::Remote);
+        assert_eq!(session.host.as_deref(), Some("10.0.0.5"));
+        assert_eq!(session.idle, Some(200));
+
+        let console = WAYLAND.replace("TYPE=wayland", "TYPE=tty");
+        assert_eq!(parse_logind(&console, 0).unwrap().0.kind, SessionKind::Console);
+    }
+
+    #[test]
+    fn skips_logind_sessions_of_others() {
+        assert!(parse_logind(&WAYLAND.replace("CLASS=user", "CLASS=greeter"), 0).is_none());
+        assert!(parse_logind(&WAYLAND.replace("STATE=active", "STATE=closing"), 0).is_none());
+    }
+}
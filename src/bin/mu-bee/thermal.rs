@@ -0,0 +1,121 @@
+//! Temperatures and clock frequencies of the CPUs.
+//!
+//! A CPU that runs too hot lowers its frequency to cool down, which makes a job run slower without
+//! any sign of it in the usage. We report the temperature sensors of the CPUs along with the
+//! frequency of each core, such that the viewer can tell a busy but throttled machine apart.
+//!
+//! Busy cores seldom reach the maximum frequency, which only a few cores can sustain at a time.
+//! The base frequency is what all cores can sustain together. On Intel CPUs, the kernel also counts
+//! how often each CPU was throttled for its temperature in `cpuN/thermal_throttle`, which tells
+//! directly whether it happened during the sampling window.
+
+use std::path::Path;
+
+use mu::model::{Sensor, Thermal};
+
+use crate::inventory::{CPU_DIR, read};
+use crate::window::Sampled;
+
+/// Names of the drivers whose sensors measure the temperature of the CPUs. Sensors of other
+/// drivers, such as those of disks, GPUs, or the motherboard, are left out.
+const CPU_SENSOR_DRIVERS: &[&str] =
+    &["coretemp", "k10temp", "zenpower", "x86_pkg_temp", "cpu_thermal", "cpu-thermal"];
+
+/// Gathers the CPU temperatures and frequencies. The CPU frequencies of `system` must be
+/// refreshed.
+pub fn gather(system: &sysinfo::System, sampled: &Sampled) -> Thermal {
+    let components = sysinfo::Components::new_with_refreshed_list();
+    let sensors = components
+        .iter()
+        .filter(|component| is_cpu_sensor(component.label()))
+        .filter_map(|component| {
+            Some(Sensor {
+                label: component.label().to_string(),
+                temperature: component.temperature().filter(|t| t.is_finite())?,
+                critical: component.critical().filter(|t| t.is_finite() && *t > 0.0),
+            })
+        })
+        .collect();
+    Thermal {
+        sensors,
+        frequencies: system.cpus().iter().map(|cpu| cpu.frequency()).collect(),
+        max_frequency: crate::inventory::max_frequency(system.cpus()),
+        base_frequency: crate::inventory::base_frequency(system.cpus()),
+        throttled_cpus: sampled.throttled_cpus,
+    }
+}
+
+/// Returns whether the sensor with the given `label`, such as `coretemp Package id 0`, measures the
+/// temperature of a CPU.
+///
+/// The label of a sensor starts with the name of its driver.
+fn is_cpu_sensor(label: &str) -> bool {
+    let driver = label.split_whitespace().next().unwrap_or_default();
+    CPU_SENSOR_DRIVERS.contains(&driver)
+}
+
+/// Reads how often each of the `cpus` has been throttled for its temperature, if the kernel counts
+/// it.
+pub fn throttle_counts(cpus: &[sysinfo::Cpu]) -> Option<Box<[u64]>> {
+    read_throttle_counts(Path::new(CPU_DIR), cpus.iter().map(|cpu| cpu.name()))
+}
+
+/// Reads the throttle counts of the CPUs with the given `names` in `cpu_dir`, if there are any.
+///
+/// Throttling of the whole package counts towards each of its CPUs.
+fn read_throttle_counts<'n>(
+    cpu_dir: &Path,
+    names: impl Iterator<Item = &'n str>,
+) -> Option<Box<[u64]>> {
+    names
+        .map(|name| {
+            let dir = cpu_dir.join(name).join("thermal_throttle");
+            let core = read::<u64>(&dir.join("core_throttle_count"))?;
+            let package = read::<u64>(&dir.join("package_throttle_count")).unwrap_or_default();
+            Some(core + package)
+        })
+        .collect()
+}
+
+/// Counts the CPUs whose throttle counts grew from `before` to `after`.
+pub fn throttled_cpus(before: &[u64], after: &[u64]) -> u32 {
+    before.iter().zip(after).filter(|&(before, after)| after > before).count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::tests::FIXTURES;
+
+    #[test]
+    fn selects_the_sensors_of_the_cpus() {
+        assert!(is_cpu_sensor("coretemp Package id 0"));
+        assert!(is_cpu_sensor("coretemp Core 3"));
+        assert!(is_cpu_sensor("k10temp Tctl"));
+        assert!(is_cpu_sensor("cpu_thermal temp1"));
+        assert!(!is_cpu_sensor("nvme Composite Samsung SSD 980 PRO 1TB temperature"));
+        assert!(!is_cpu_sensor("amdgpu edge"));
+        assert!(!is_cpu_sensor("acpitz temp1"));
+        assert!(!is_cpu_sensor(""));
+    }
+
+    #[test]
+    fn reads_the_throttle_counts() {
+        let names = (0..8).map(|n| format!("cpu{n}")).collect::<Vec<_>>();
+        let xeon = Path::new(FIXTURES).join("xeon");
+        let counts = read_throttle_counts(&xeon, names.iter().map(String::as_str)).unwrap();
+        assert_eq!(&*counts, [0, 0, 15, 3, 0, 0, 3, 3]);
+        // Only some CPUs count their throttling.
+        let vm = Path::new(FIXTURES).join("arm-vm");
+        assert_eq!(read_throttle_counts(&vm, ["cpu0", "cpu1"].into_iter()), None);
+    }
+
+    #[test]
+    fn counts_the_cpus_whose_counters_grew() {
+        assert_eq!(throttled_cpus(&[0, 0, 15, 3], &[0, 0, 15, 3]), 0);
+        assert_eq!(throttled_cpus(&[0, 0, 15, 3], &[0, 1, 15, 5]), 2);
+        assert_eq!(throttled_cpus(&[], &[]), 0);
+        // A counter that went down, such as after a CPU came back online, is no throttling.
+        assert_eq!(throttled_cpus(&[7, 0], &[0, 0]), 0);
+    }
+}
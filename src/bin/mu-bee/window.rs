@@ -0,0 +1,287 @@
+//! Sampling of the CPU usage and the I/O throughput over a window of time.
+//!
+//! A single sample only tells how busy the machine was during a fraction of a second, which makes
+//! bursty workloads flicker from one run of the hive to the next. Instead, we take a number of
+//! samples spread over a window, and report the mean and the peak usage over that window.
+//!
+//! The usage of a process in each sample is derived from the CPU time it accumulated since the
+//! previous sample. Processes that exit during the window are kept around by [`sample`], such
+//! that the CPU time they spent within the window still counts towards their mean, and towards
+//! their job (see [`crate::jobs`]).
+//!
+//! The I/O throughput needs no intermediate samples. It is derived from running totals of bytes,
+//! which are compared between the start and the end of the window (see [`crate::io`]). So are the
+//! thermal throttle counters of the CPUs (see [`crate::thermal`]).
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use mu::model::{DiskIo, NetworkIo, ProcessIo};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+use crate::io::{Counters, rate};
+
+/// Mean and peak CPU usage over a window, in percent of a single core.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuStats {
+    pub mean: f32,
+    pub peak: f32,
+}
+
+/// The CPU usage sampled over a window.
+#[derive(Debug, Default)]
+pub struct Sampled {
+    /// Time between the first and the last sample.
+    pub window: Duration,
+    pub samples: u32,
+    /// Mean and peak usage of all cores.
+    pub global: CpuStats,
+    /// Mean and peak usage of each core.
+    pub cpus: Box<[CpuStats]>,
+    /// Mean and peak usage of each process, by pid and start time.
+    processes: HashMap<(Pid, u64), CpuStats>,
+    /// Mean throughput of each network interface.
+    pub networks: Box<[NetworkIo]>,
+    /// Mean throughput of each block device.
+    pub disks: Box<[DiskIo]>,
+    /// Mean disk throughput of each process, by pid and start time.
+    io: HashMap<(Pid, u64), ProcessIo>,
+    /// Process group of each process, by pid and start time, as it was when it was first seen.
+    pgids: HashMap<(Pid, u64), u32>,
+    /// Number of CPUs that were throttled for their temperature, if the kernel counts it.
+    pub throttled_cpus: Option<u32>,
+}
+
+impl Sampled {
+    /// Returns the mean and peak usage of a process over the window.
+    pub fn process(&self, proc: &sysinfo::Process) -> CpuStats {
+        let key = (proc.pid(), proc.start_time());
+        self.processes.get(&key).copied().unwrap_or_default()
+    }
+
+    /// Returns the process group of a process, if it was seen during the window.
+    ///
+    /// This is known even for processes that have exited since.
+    pub fn pgid(&self, proc: &sysinfo::Process) -> Option<u32> {
+        self.pgids.get(&(proc.pid(), proc.start_time())).copied()
+    }
+
+    /// Returns the mean disk throughput of a process over the window.
+    ///
+    /// This is zero for processes whose I/O counters cannot be read (see [`crate::io`]).
+    pub fn process_io(&self, proc: &sysinfo::Process) -> ProcessIo {
+        let key = (proc.pid(), proc.start_time());
+        self.io.get(&key).copied().unwrap_or_default()
+    }
+}
+
+/// Running sums towards [`CpuStats`].
+#[derive(Debug, Clone, Copy, Default)]
+struct Accumulator {
+    sum: f32,
+    peak: f32,
+}
+
+impl Accumulator {
+    fn add(&mut self, usage: f32) {
+        self.sum += usage;
+        self.peak = self.peak.max(usage);
+    }
+
+    fn stats(&self, samples: u32) -> CpuStats {
+        CpuStats { mean: self.sum / samples.max(1) as f32, peak: self.peak }
+    }
+}
+
+/// Adds the usage of each process over the `elapsed` time between two samples to its accumulator.
+///
+/// The CPU times are in milliseconds. A process that has exited no longer accumulates any, and a
+/// process that started within the window accumulated all of it since.
+fn add_process_samples(
+    processes: &mut HashMap<(Pid, u64), Accumulator>,
+    before: &HashMap<(Pid, u64), u64>,
+    after: &HashMap<(Pid, u64), u64>,
+    elapsed: Duration,
+) {
+    let elapsed = elapsed.as_secs_f32() * 1000.0;
+    for (&key, &time) in after {
+        let before = before.get(&key).copied().unwrap_or_default();
+        let usage = 100.0 * time.saturating_sub(before) as f32 / elapsed;
+        processes.entry(key).or_default().add(usage);
+    }
+}
+
+/// Samples the CPU usage of the cores and the processes `samples` times, spread over `window`, and
+/// measures the I/O throughput over the same window.
+///
+/// The samples are never closer together than [`sysinfo::MINIMUM_CPU_UPDATE_INTERVAL`], so a
+/// short window with many samples takes longer than asked for. Afterwards, the processes in
+/// `system` are refreshed, including the ones that exited during the window.
+pub fn sample(system: &mut System, window: Duration, samples: u32) -> Sampled {
+    let samples = samples.max(1);
+    let interval = (window / samples).max(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    // Exited processes are not removed, so the identity of processes that come and go within the
+    // window is picked up as soon as they are first seen.
+    let refresh_kind = ProcessRefreshKind::nothing()
+        .with_cpu()
+        .with_memory()
+        .with_user(UpdateKind::OnlyIfNotSet)
+        .with_cmd(UpdateKind::OnlyIfNotSet)
+        .with_exe(UpdateKind::OnlyIfNotSet)
+        .with_disk_usage()
+        .with_tasks();
+    let cpu_times = |system: &System| {
+        system
+            .processes()
+            .values()
+            .map(|proc| ((proc.pid(), proc.start_time()), proc.accumulated_cpu_time()))
+            .collect::<HashMap<_, _>>()
+    };
+    let io_totals = |system: &System| {
+        system
+            .processes()
+            .values()
+            .map(|proc| {
+                let usage = proc.disk_usage();
+                (
+                    (proc.pid(), proc.start_time()),
+                    (usage.total_read_bytes, usage.total_written_bytes),
+                )
+            })
+            .collect::<HashMap<_, _>>()
+    };
+
+    // The process group of a process cannot be looked up once it has exited.
+    let mut pgids = HashMap::new();
+    let mut record_pgids = |system: &System| {
+        for proc in system.processes().values().filter(|proc| proc.thread_kind().is_none()) {
+            let key = (proc.pid(), proc.start_time());
+            if !pgids.contains_key(&key)
+                && let Some(pgid) = crate::jobs::pgid(proc.pid())
+            {
+                pgids.insert(key, pgid);
+            }
+        }
+    };
+
+    system.refresh_cpu_usage();
+    system.refresh_processes_specifics(ProcessesToUpdate::All, false, refresh_kind);
+    record_pgids(system);
+    let start = Instant::now();
+    let mut previous = (start, cpu_times(system));
+    let counters = Counters::read();
+    let io_before = io_totals(system);
+    let throttle_before = crate::thermal::throttle_counts(system.cpus());
+
+    let mut global = Accumulator::default();
+    let mut cpus = vec![Accumulator::default(); system.cpus().len()];
+    let mut processes = HashMap::<(Pid, u64), Accumulator>::new();
+    for _ in 0..samples {
+        std::thread::sleep(interval);
+        system.refresh_cpu_usage();
+        system.refresh_processes_specifics(ProcessesToUpdate::All, false, refresh_kind);
+        record_pgids(system);
+        let now = Instant::now();
+        let times = cpu_times(system);
+
+        global.add(system.global_cpu_usage());
+        for (acc, cpu) in cpus.iter_mut().zip(system.cpus()) {
+            acc.add(cpu.cpu_usage());
+        }
+        let elapsed = now.duration_since(previous.0);
+        add_process_samples(&mut processes, &previous.1, &times, elapsed);
+        previous = (now, times);
+    }
+
+    // A process that started within the window did all of its I/O since.
+    let window = previous.0.duration_since(start);
+    let (networks, disks) = crate::io::rates(&counters, &Counters::read(), window);
+    let throttled_cpus = throttle_before
+        .zip(crate::thermal::throttle_counts(system.cpus()))
+        .map(|(before, after)| crate::thermal::throttled_cpus(&before, &after));
+    let io = io_totals(system)
+        .into_iter()
+        .map(|(key, (read, written))| {
+            let (read_before, written_before) = io_before.get(&key).copied().unwrap_or_default();
+            (
+                key,
+                ProcessIo {
+                    read: rate(read, read_before, window),
+                    written: rate(written, written_before, window),
+                },
+            )
+        })
+        .collect();
+
+    Sampled {
+        window,
+        samples,
+        global: global.stats(samples),
+        cpus: cpus.iter().map(|acc| acc.stats(samples)).collect(),
+        processes: processes.into_iter().map(|(key, acc)| (key, acc.stats(samples))).collect(),
+        networks,
+        disks,
+        io,
+        pgids,
+        throttled_cpus,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_mean_and_peak() {
+        let mut acc = Accumulator::default();
+        for usage in [10.0, 50.0, 30.0, 10.0] {
+            acc.add(usage);
+        }
+        let stats = acc.stats(4);
+        assert_eq!(stats.mean, 25.0);
+        assert_eq!(stats.peak, 50.0);
+        assert_eq!(Accumulator::default().stats(0).mean, 0.0);
+    }
+
+    #[test]
+    fn counts_the_cpu_time_of_processes_within_the_window() {
+        let (steady, exited, started) =
+            ((Pid::from(1), 10), (Pid::from(2), 20), (Pid::from(3), 30));
+        let second = Duration::from_secs(1);
+        let samples = [
+            HashMap::from([(steady, 1000), (exited, 5000)]),
+            HashMap::from([(steady, 1500), (exited, 5800)]),
+            // The exited process is kept around with the CPU time it had when it exited.
+            HashMap::from([(steady, 2000), (exited, 5800), (started, 400)]),
+            HashMap::from([(steady, 2500), (exited, 5800), (started, 1400)]),
+        ];
+        let mut processes = HashMap::new();
+        for pair in samples.windows(2) {
+            add_process_samples(&mut processes, &pair[0], &pair[1], second);
+        }
+        let stats = |key| processes[&key].stats(3);
+
+        assert_eq!(stats(steady).mean, 50.0);
+        assert_eq!(stats(steady).peak, 50.0);
+        assert!((stats(exited).mean - 80.0 / 3.0).abs() < 1e-4);
+        assert_eq!(stats(exited).peak, 80.0);
+        // A process that started between samples accumulated all of its time since.
+        assert!((stats(started).mean - 140.0 / 3.0).abs() < 1e-4);
+        assert_eq!(stats(started).peak, 100.0);
+    }
+
+    #[test]
+    fn spreads_the_samples_over_the_window() {
+        let mut system = System::new();
+        let window = 3 * sysinfo::MINIMUM_CPU_UPDATE_INTERVAL;
+        let sampled = sample(&mut system, window, 2);
+        assert_eq!(sampled.samples, 2);
+        // Samples are never closer together than the shortest interval that can be measured.
+        assert!(sampled.window >= window, "{:?}", sampled.window);
+        assert_eq!(sampled.cpus.len(), system.cpus().len());
+        assert!(sampled.global.mean <= sampled.global.peak);
+        let pid = sysinfo::get_current_pid().unwrap();
+        let this = system.process(pid).unwrap();
+        assert_eq!(sampled.pgid(this), crate::jobs::pgid(pid));
+    }
+}
@@ -0,0 +1,32 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use crate::config::MachineDefinitions;
+
+#[derive(Debug, clap::Args)]
+pub struct CheckArgs {
+    /// Path to an `.ini` formatted file listing all machines for each room.
+    #[clap(long, short)]
+    machines: PathBuf,
+}
+
+/// Checks the machines file and reports any problems with it.
+///
+/// Fails if any problems were found, such that this can be used before committing edits.
+pub fn run(args: CheckArgs) -> Result<()> {
+    let path = &args.machines;
+    let (machines, problems) = MachineDefinitions::read(path)
+        .context(format!("could not process machines file {path:?}"))?;
+    for problem in &problems {
+        println!("{}:{}: {}", path.display(), problem.line, problem.message);
+    }
+
+    if !problems.is_empty() {
+        anyhow::bail!("found {} problem(s) in {path:?}", problems.len());
+    }
+    let rooms = machines.iter().map(|machine| &machine.room).collect::<HashSet<_>>();
+    println!("{path:?} lists {} machines in {} rooms.", machines.len(), rooms.len());
+    Ok(())
+}
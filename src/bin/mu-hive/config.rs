@@ -1,8 +1,18 @@
-use std::{io::Read, path::Path, str::FromStr};
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use mu::model::Owner;
 
+use crate::log::warning;
+
+/// Room of the machines that are listed before any room header.
+const ORPHAN_ROOM: &str = "orphan";
+
 #[derive(Debug, Clone)]
 pub struct MachineDefinitions(Box<[MachineDefinition]>);
 
@@ -10,17 +20,59 @@ pub struct MachineDefinitions(Box<[MachineDefinition]>);
 pub struct MachineDefinition {
     pub room: String,
     pub hostname: String,
-    /// Note or name of owner.
+    /// Owner of the machine, as parsed from its note.
     ///
     /// Not all machines have such information associated with them.
-    pub note: Option<String>,
+    pub owner: Owner,
+    /// How the bee on this machine is reached.
+    pub transport: TransportKind,
+    /// Settings for connecting to this machine over ssh.
+    pub ssh: SshSettings,
+    /// Path to the bee on this machine, overriding the path provided to the hive.
+    pub bee: Option<String>,
+    /// Path to the config file passed to the bee on this machine.
+    pub bee_config: Option<String>,
+    /// Time over which the bee on this machine samples the CPU usage, if not its default.
+    pub window: Option<std::time::Duration>,
+    /// Number of samples the bee on this machine takes over its window, if not its default.
+    pub samples: Option<u32>,
+    /// Free-form labels for this machine, such as `gpu` or `loaner`.
+    pub tags: Box<[String]>,
+    /// Whether the usage of this machine should be gathered at all.
+    pub enabled: bool,
+}
+
+/// Settings for connecting to a machine over ssh.
+///
+/// Anything that is not set here is left to the ssh configuration of the user running the hive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SshSettings {
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    /// Path to the private key to authenticate with.
+    pub identity: Option<PathBuf>,
+    /// Jump host to connect through, as `[user@]host[:port]`.
+    pub jump: Option<String>,
+}
+
+/// How the hive reaches the bee on a machine.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TransportKind {
+    /// Over an ssh session.
+    #[default]
+    Ssh,
+    /// By executing the bee directly on the machine running the hive.
+    Local,
+    /// By executing a command template through the local shell.
+    ///
+    /// See [`crate::transport::Template`] for the placeholders that can be used.
+    Command(String),
 }
 
 impl From<MachineDefinition> for mu::model::MachineDefinition {
     fn from(definition: MachineDefinition) -> Self {
-        let MachineDefinition { room, hostname, note } = definition;
-        let owner = note.map(|note| Owner::from_str(&note).unwrap()).unwrap_or_default();
-        Self { hostname, owner, room }
+        let MachineDefinition { room, hostname, owner, tags, .. } = definition;
+        Self { hostname, owner, room, tags }
     }
 }
 
@@ -39,18 +91,70 @@ impl MachineDefinitions {
     /// Under each header, the machines that belong to that room are listed.
     /// Each machine listing starts with the machine hostname, a colon, a space, and finally the
     /// name or note describing who that machine belongs to.
+    ///
+    /// The note may be followed by a `|` and a list of `key=value` attributes, separated by
+    /// whitespace. Values that contain whitespace can be surrounded by double quotes.
+    ///
+    /// The following attributes are understood:
+    ///
+    /// - `user`, `port`, `identity`, `jump`: ssh connection settings (see [`SshSettings`]),
+    /// - `transport`: one of `ssh` (the default), `local`, or `command`,
+    /// - `command`: the command template for the `command` transport, in which `{host}`, `{user}`,
+    ///   `{port}`, and `{bee}` are filled in,
+    /// - `bee`: path to the bee on this machine,
+    /// - `bee-config`: path to the config file for the bee on this machine,
+    /// - `tags`: comma-separated list of labels,
+    /// - `enabled`: `true` (the default) or `false`, to skip this machine.
+    ///
+    /// ```ini
+    /// [office]
+    /// alan: Jan Stevens
+    /// herman: Reservation Required | user=mu port=2222 tags=gpu,loaner
+    /// hive: | transport=local
+    /// box: | transport=command command="docker exec {host} {bee}"
+    /// broken: Jan Stevens | enabled=false
+    /// ```
+    ///
+    /// Problems with the file are reported as warnings, and are otherwise dealt with leniently, as
+    /// they have always been. Machines listed before any room header are put in the `orphan` room,
+    /// and machines listed more than once are gathered for each listing. Only lines that cannot be
+    /// parsed into a machine are skipped. Use [`MachineDefinitions::read`] to get at the problems
+    /// themselves, as `mu-hive check` does.
     pub fn read_from_config(path: impl AsRef<Path>) -> Result<MachineDefinitions> {
+        let path = path.as_ref();
+        let (machines, problems) = Self::read(path)?;
+        for problem in problems {
+            warning!("In {path:?}, {problem}.");
+        }
+        Ok(machines)
+    }
+
+    /// Reads and parses the machines file at `path`, along with any problems found in it.
+    ///
+    /// See [`MachineDefinitions::read_from_config`] for the format.
+    pub fn read(path: impl AsRef<Path>) -> Result<(MachineDefinitions, Vec<Problem>)> {
         let path = path.as_ref();
         let mut s = String::new();
         std::fs::File::open(path)
-            .context(format!("could not open machines file"))?
+            .context("could not open machines file")?
             .read_to_string(&mut s)
-            .context(format!("could not read machines file"))?;
+            .context("could not read machines file")?;
+        Ok(Self::parse(&s))
+    }
 
+    /// Parses the contents of a machines file, along with any problems found in it.
+    pub fn parse(s: &str) -> (MachineDefinitions, Vec<Problem>) {
         let mut machines_config = Vec::new();
-        let mut room = None;
-        let mut lines = s.lines();
-        while let Some(line) = lines.next() {
+        let mut problems = Vec::new();
+        // The current room, the line of its header, and the number of machine lines under it.
+        let mut room: Option<(&str, usize, usize)> = None;
+        // Where each hostname was first listed.
+        let mut listed = HashMap::<String, (String, usize)>::new();
+        let empty_room = |(name, ln, count): (&str, usize, usize)| {
+            (count == 0).then(|| Problem::new(ln, format!("room [{name}] lists no machines")))
+        };
+        for (ln, line) in s.lines().enumerate() {
+            let ln = ln + 1;
             // Strip any comments.
             let line = match line.split_once('#') {
                 Some((line, _comment)) => line,
@@ -69,27 +173,300 @@ impl MachineDefinitions {
             {
                 // A room header is surrounded by brackets.
                 let header = header.trim(); // "Tighten up those lines!"
-                room = Some(header);
-            } else {
-                // Otherwise, we're dealing with a machine line.
-                // TODO: Consider whether we want to error out on that or just provide the 'orphan' placeholder.
-                let room = room.unwrap_or("orphan").to_string();
-                // Parse out the hostname and note/owner information. If the line does not have the
-                // expected format, give a warning and skip the line.
-                let Some((hostname, note)) = line.split_once(':') else {
+                if header.is_empty() {
+                    problems.push(Problem::new(ln, "room header without a name"));
+                }
+                problems.extend(room.and_then(empty_room));
+                room = Some((header, ln, 0));
+                continue;
+            }
+
+            // Otherwise, we're dealing with a machine line.
+            let room = match &mut room {
+                Some((room, _, count)) => {
+                    *count += 1;
+                    *room
+                }
+                None => {
+                    problems.push(Problem::new(
+                        ln,
+                        format!("machine listed before any [room] header, put in [{ORPHAN_ROOM}]"),
+                    ));
+                    ORPHAN_ROOM
+                }
+            };
+            let (machine, owner_error) = match parse_machine(room, line) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    problems.push(Problem::new(ln, format!("{e:#}")));
                     continue;
+                }
+            };
+            if let Some(e) = owner_error {
+                problems.push(Problem::new(ln, format!("unexpected owner note: {e}")));
+            }
+            match listed.get(&machine.hostname) {
+                Some((first_room, first_ln)) if first_room == room => problems.push(Problem::new(
+                    ln,
+                    format!("{:?} is already listed on line {first_ln}", machine.hostname),
+                )),
+                Some((first_room, first_ln)) => problems.push(Problem::new(
+                    ln,
+                    format!(
+                        "{:?} is already listed in room [{first_room}] on line {first_ln}",
+                        machine.hostname
+                    ),
+                )),
+                None => {
+                    listed.insert(machine.hostname.clone(), (room.to_string(), ln));
+                }
+            }
+            machines_config.push(machine);
+        }
+        problems.extend(room.and_then(empty_room));
+        problems.sort_by_key(|problem| problem.line);
+
+        (Self(machines_config.into_boxed_slice()), problems)
+    }
+}
+
+/// A problem with a line of the machines file.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Problem {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self { line, message: message.into() }
+    }
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parses a machine line of the form `hostname: note | key=value ...`.
+///
+/// The owner is only used for display, so a note that does not parse as expected is no reason to
+/// reject the machine. It is taken to be the name of a member, and the error is returned alongside.
+fn parse_machine(
+    room: &str,
+    line: &str,
+) -> Result<(MachineDefinition, Option<mu::model::ParseOwnerError>)> {
+    let Some((hostname, note)) = line.split_once(':') else {
+        bail!("expected a line of the form 'hostname: note', but found {line:?}");
+    };
+    let hostname = hostname.trim();
+    if hostname.is_empty() || hostname.contains(char::is_whitespace) {
+        bail!("invalid hostname {hostname:?}");
+    }
+    let (note, attributes) = match note.split_once('|') {
+        Some((note, attributes)) => (note, parse_attributes(attributes)?),
+        None => (note, Vec::new()),
+    };
+    let note = note.trim();
+    let (owner, owner_error) = match Owner::from_str(note) {
+        Ok(owner) => (owner, None),
+        Err(e) => (Owner::Member(note.to_string()), Some(e)),
+    };
+    let mut machine = MachineDefinition {
+        room: room.to_string(),
+        hostname: hostname.to_string(),
+        owner,
+        transport: TransportKind::default(),
+        ssh: SshSettings::default(),
+        bee: None,
+        bee_config: None,
+        window: None,
+        samples: None,
+        tags: Box::default(),
+        enabled: true,
+    };
+    let mut command = None;
+    for (key, value) in attributes {
+        if key == "command" {
+            command = Some(value);
+            continue;
+        }
+        machine.set(&key, value)?;
+    }
+    if let TransportKind::Command(template) = &mut machine.transport {
+        let Some(command) = command else {
+            bail!("the command transport requires a 'command' attribute");
+        };
+        for name in crate::transport::placeholders(&command) {
+            match name {
+                "user" if machine.ssh.user.is_none() => {
+                    bail!("the command template uses {{user}}, but no 'user' attribute is set")
+                }
+                "port" if machine.ssh.port.is_none() => {
+                    bail!("the command template uses {{port}}, but no 'port' attribute is set")
+                }
+                known if crate::transport::PLACEHOLDERS.contains(&known) => {}
+                unknown => bail!("unknown placeholder {{{unknown}}} in the command template"),
+            }
+        }
+        *template = command;
+    }
+    Ok((machine, owner_error))
+}
+
+impl MachineDefinition {
+    /// Sets the attribute `key` to `value`.
+    ///
+    /// The `command` attribute is handled separately, since it depends on the transport.
+    fn set(&mut self, key: &str, value: String) -> Result<()> {
+        match key {
+            "transport" => {
+                self.transport = match value.as_str() {
+                    "ssh" => TransportKind::Ssh,
+                    "local" => TransportKind::Local,
+                    // The template itself is set through the 'command' attribute.
+                    "command" => TransportKind::Command(String::new()),
+                    unknown => bail!("unknown transport {unknown:?}"),
+                }
+            }
+            "user" => self.ssh.user = Some(value),
+            "port" => {
+                let port = value.parse().context(format!("invalid port {value:?}"))?;
+                self.ssh.port = Some(port);
+            }
+            "identity" => self.ssh.identity = Some(PathBuf::from(value)),
+            "jump" => self.ssh.jump = Some(value),
+            "bee" => self.bee = Some(value),
+            "bee-config" => self.bee_config = Some(value),
+            "window" => {
+                let window = value
+                    .parse::<f32>()
+                    .ok()
+                    .and_then(|w| std::time::Duration::try_from_secs_f32(w).ok())
+                    .filter(|w| !w.is_zero());
+                let Some(window) = window else {
+                    bail!("expected a positive number of seconds for 'window', but found {value:?}")
                 };
-                let hostname = hostname.trim().to_string();
-                // TODO: Is there a more elegant way to do this? Like an inverse Default::default()?
-                let note = match note.trim() {
-                    "" => None,
-                    note => Some(note.to_string()),
+                self.window = Some(window);
+            }
+            "samples" => {
+                let samples = value.parse::<u32>().ok().filter(|&samples| samples > 0);
+                let Some(samples) = samples else {
+                    bail!("expected a positive number for 'samples', but found {value:?}")
                 };
-                let machine = MachineDefinition { room, hostname, note };
-                machines_config.push(machine);
+                self.samples = Some(samples);
+            }
+            "tags" => {
+                self.tags = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_string)
+                    .collect();
             }
+            "enabled" => {
+                self.enabled = match value.as_str() {
+                    "true" | "yes" => true,
+                    "false" | "no" => false,
+                    _ => bail!("expected 'true' or 'false' for 'enabled', but found {value:?}"),
+                }
+            }
+            unknown => bail!("unknown attribute {unknown:?}"),
         }
+        Ok(())
+    }
+}
+
+/// Parses a whitespace-separated list of `key=value` attributes.
+///
+/// Values may be surrounded by double quotes, in which case they can contain whitespace.
+fn parse_attributes(s: &str) -> Result<Vec<(String, String)>> {
+    let mut attributes = Vec::new();
+    let mut rest = s.trim_start();
+    while !rest.is_empty() {
+        let Some((key, tail)) = rest.split_once('=') else {
+            bail!("expected an attribute of the form 'key=value', but found {rest:?}");
+        };
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            bail!("expected an attribute key before '=', but found {key:?}");
+        }
+        let (value, tail) = match tail.strip_prefix('"') {
+            Some(quoted) => match quoted.split_once('"') {
+                Some((value, tail)) => (value, tail),
+                None => bail!("missing a closing quote for the value of {key:?}"),
+            },
+            None => tail.split_once(char::is_whitespace).unwrap_or((tail, "")),
+        };
+        attributes.push((key.to_string(), value.to_string()));
+        rest = tail.trim_start();
+    }
+    Ok(attributes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_problems_by_line() {
+        let (machines, problems) = MachineDefinitions::parse(
+            "early: Jan Stevens\n\
+             [office]\n\
+             alan: Jan Stevens\n\
+             no colon here\n\
+             herman: | port=abc\n\
+             alan: Jan Stevens # twice\n\
+             []\n\
+             [server room]\n\
+             alan:\n\
+             [empty]\n",
+        );
+        let problems = problems
+            .iter()
+            .map(|problem| (problem.line, problem.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(problems.len(), 8, "{problems:#?}");
+        assert_eq!(problems[0], (1, "machine listed before any [room] header, put in [orphan]"));
+        assert!(problems[1].0 == 4 && problems[1].1.starts_with("expected a line of the form"));
+        assert!(problems[2].0 == 5 && problems[2].1.starts_with("invalid port"));
+        assert_eq!(problems[3], (6, "\"alan\" is already listed on line 3"));
+        assert_eq!(problems[4], (7, "room header without a name"));
+        assert_eq!(problems[5], (7, "room [] lists no machines"));
+        assert_eq!(problems[6], (9, "\"alan\" is already listed in room [office] on line 3"));
+        assert_eq!(problems[7], (10, "room [empty] lists no machines"));
+
+        // At runtime, only the lines that do not describe a machine at all are skipped.
+        let machines = machines
+            .iter()
+            .map(|machine| (machine.room.as_str(), machine.hostname.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            machines,
+            [("orphan", "early"), ("office", "alan"), ("office", "alan"), ("server room", "alan")]
+        );
+    }
 
-        Ok(Self(machines_config.into_boxed_slice()))
+    #[test]
+    fn parses_machine_attributes() {
+        let (machines, problems) = MachineDefinitions::parse(
+            "[lab]\n\
+             ernst: Piet de Vries | user=mu port=2222 jump=gateway tags=gpu,,loaner\n\
+             box: | transport=command command=\"docker exec {host} {bee}\" enabled=no\n\
+             noether: | window=2.5 samples=5\n",
+        );
+        assert!(problems.is_empty(), "{problems:?}");
+        let ernst = &machines[0];
+        assert_eq!(ernst.ssh.user.as_deref(), Some("mu"));
+        assert_eq!(ernst.ssh.port, Some(2222));
+        assert_eq!(ernst.ssh.jump.as_deref(), Some("gateway"));
+        assert_eq!(&*ernst.tags, ["gpu", "loaner"]);
+        assert!(ernst.enabled);
+        let command = TransportKind::Command("docker exec {host} {bee}".to_string());
+        assert_eq!(machines[1].transport, command);
+        assert!(!machines[1].enabled);
+        assert_eq!(machines[2].window, Some(std::time::Duration::from_millis(2500)));
+        assert_eq!(machines[2].samples, Some(5));
     }
 }
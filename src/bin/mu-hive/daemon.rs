@@ -0,0 +1,149 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::signal::unix::{SignalKind, signal};
+
+use crate::log::{info, warning};
+use crate::sessions::Sessions;
+use crate::{Limits, RunArgs, lock, round};
+
+#[derive(Debug, clap::Args)]
+pub struct DaemonArgs {
+    #[clap(flatten)]
+    run: RunArgs,
+    #[clap(flatten)]
+    limits: Limits,
+    /// Seconds between the starts of consecutive rounds.
+    ///
+    /// This should be comfortably longer than the deadline of a round.
+    #[clap(long, default_value = "60", value_parser = crate::parse_seconds)]
+    interval: Duration,
+    /// Maximum number of seconds that is randomly added to each interval.
+    ///
+    /// This keeps the rounds from lining up with other periodic jobs on the machines.
+    #[clap(long, default_value = "5", value_parser = crate::parse_seconds)]
+    jitter: Duration,
+    /// Path for writing the log file. Without it, logs are written to stderr.
+    #[clap(long)]
+    log: Option<PathBuf>,
+    /// Size in bytes after which the log file is rotated.
+    #[clap(long, default_value_t = 10 * 1024 * 1024)]
+    log_max_size: u64,
+    /// Number of rotated log files to keep, as `<log>.1` (most recent) up to `<log>.<log-keep>`.
+    #[clap(long, default_value_t = 3)]
+    log_keep: usize,
+}
+
+/// Runs the hive as a daemon until it receives SIGTERM or SIGINT.
+///
+/// The ssh sessions to the machines are kept open between rounds.
+pub fn run(args: DaemonArgs) -> Result<()> {
+    if let Some(log_path) = &args.log {
+        crate::log::to_file(log_path.clone(), args.log_max_size, args.log_keep)?;
+    }
+
+    // The lock is held for as long as the daemon lives, such that a stray single run cannot
+    // interfere with it.
+    let lock_path = args.run.lock_path();
+    let Some(_lock) = lock(&lock_path)? else {
+        anyhow::bail!("another run is holding {lock_path:?}");
+    };
+
+    let runtime = tokio::runtime::Runtime::new().context("could not set up async runtime")?;
+    runtime.block_on(async {
+        let sessions = Sessions::default();
+        let result = rounds(&args, &sessions).await;
+        info!("Shutting down. Closing sessions.");
+        sessions.close().await;
+        result
+    })
+}
+
+async fn rounds(args: &DaemonArgs, sessions: &Sessions) -> Result<()> {
+    let mut terminate = signal(SignalKind::terminate()).context("could not listen for SIGTERM")?;
+    let mut interrupt = signal(SignalKind::interrupt()).context("could not listen for SIGINT")?;
+    let interval = args.interval;
+
+    info!("Starting daemon with an interval of {interval:?}.");
+    loop {
+        let start = tokio::time::Instant::now();
+        let round = round(&args.run, &args.limits, sessions);
+        tokio::pin!(round);
+        let stop = tokio::select! {
+            result = &mut round => {
+                log_round(result, start);
+                false
+            }
+            _ = terminate.recv() => true,
+            _ = interrupt.recv() => true,
+        };
+        if stop {
+            // A round is bounded by its deadline, so we can afford to let it finish.
+            info!("Received a signal. Finishing the current round.");
+            log_round(round.await, start);
+            return Ok(());
+        }
+
+        let next = next_round(start, interval, args.jitter);
+        tokio::select! {
+            _ = tokio::time::sleep_until(next) => {}
+            _ = terminate.recv() => return Ok(()),
+            _ = interrupt.recv() => return Ok(()),
+        }
+    }
+}
+
+fn log_round(result: Result<()>, start: tokio::time::Instant) {
+    let duration = start.elapsed().as_secs_f32();
+    match result {
+        Ok(()) => info!("Round took {duration:.2} s."),
+        // A failed round is no reason to stop. The next one may well succeed.
+        Err(e) => warning!("Round failed after {duration:.2} s: {e:#}"),
+    }
+}
+
+/// Returns when the round after the one that began at `start` is due.
+fn next_round(
+    start: tokio::time::Instant,
+    interval: Duration,
+    max_jitter: Duration,
+) -> tokio::time::Instant {
+    start + interval + jitter(max_jitter)
+}
+
+/// Returns a pseudo-random duration between zero and `max`.
+fn jitter(max: Duration) -> Duration {
+    // We have no need for proper randomness here, so the sub-second clock will do.
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    let fraction = nanos as f32 / 1e9;
+    max.mul_f32(fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jitters_up_to_the_maximum() {
+        assert_eq!(jitter(Duration::ZERO), Duration::ZERO);
+        for _ in 0..100 {
+            assert!(jitter(Duration::from_secs(5)) < Duration::from_secs(5));
+        }
+    }
+
+    #[test]
+    fn schedules_rounds_by_their_start() {
+        let start = tokio::time::Instant::now();
+        let interval = Duration::from_secs(60);
+        assert_eq!(next_round(start, interval, Duration::ZERO), start + interval);
+        let next = next_round(start, interval, Duration::from_secs(5));
+        assert!(next >= start + interval && next < start + interval + Duration::from_secs(5));
+        // The longest durations allowed on the command line still make for a valid instant.
+        let next = next_round(start, crate::MAX_SECONDS, crate::MAX_SECONDS);
+        assert!(next >= start + crate::MAX_SECONDS);
+    }
+}
@@ -0,0 +1,140 @@
+//! Logging for the hive.
+//!
+//! By default, messages are written to stderr. When running as a daemon, messages can be written
+//! to a log file instead, which is rotated once it grows too large.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+
+use crate::output::{shift_snapshots, snapshot_path};
+
+static LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+struct LogFile {
+    path: PathBuf,
+    file: std::fs::File,
+    size: u64,
+    max_size: u64,
+    keep: usize,
+}
+
+impl LogFile {
+    fn open(path: PathBuf, max_size: u64, keep: usize) -> Result<Self> {
+        let file = std::fs::File::options()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .context(format!("could not open log file {path:?}"))?;
+        let size = file.metadata().map(|metadata| metadata.len()).unwrap_or_default();
+        Ok(Self { path, file, size, max_size, keep })
+    }
+
+    /// Moves the current log file to `path.1`, shifting up older log files, and starts a new one.
+    ///
+    /// Only the `keep` most recent log files are retained.
+    fn rotate(&mut self) -> Result<()> {
+        let path = &self.path;
+        if self.keep == 0 {
+            std::fs::remove_file(path).context(format!("could not remove {path:?}"))?;
+        } else {
+            shift_snapshots(path, self.keep)?;
+            let to = snapshot_path(path, 1);
+            std::fs::rename(path, &to).context(format!("could not move {path:?} to {to:?}"))?;
+        }
+        *self = Self::open(self.path.clone(), self.max_size, self.keep)?;
+        Ok(())
+    }
+
+    fn write(&mut self, line: &str) -> std::io::Result<()> {
+        if self.size >= self.max_size
+            && let Err(e) = self.rotate()
+        {
+            // We'll just keep writing to the current file, then.
+            eprintln!("WARNING: Could not rotate log file: {e:#}");
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// Writes all following messages to the log file at `path` rather than to stderr.
+///
+/// The log file is rotated once it is larger than `max_size` bytes, keeping `keep` old log files.
+pub fn to_file(path: PathBuf, max_size: u64, keep: usize) -> Result<()> {
+    let log_file = LogFile::open(path, max_size, keep)?;
+    *LOG_FILE.lock().unwrap() = Some(log_file);
+    Ok(())
+}
+
+/// Writes a message with the provided `level`.
+///
+/// Use the [`info`] and [`warning`] macros rather than calling this directly.
+pub fn write(level: &str, message: std::fmt::Arguments) {
+    let mut log_file = LOG_FILE.lock().unwrap();
+    match log_file.as_mut() {
+        Some(log_file) => {
+            let timestamp = chrono::offset::Local::now().to_rfc3339();
+            let line = format!("{timestamp} {level}: {message}\n");
+            if let Err(e) = log_file.write(&line) {
+                eprintln!("WARNING: Could not write to log file: {e}");
+                eprintln!("{level}: {message}");
+            }
+        }
+        None => eprintln!("{level}: {message}"),
+    }
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::log::write("INFO", format_args!($($arg)*)) };
+}
+
+macro_rules! warning {
+    ($($arg:tt)*) => { $crate::log::write("WARNING", format_args!($($arg)*)) };
+}
+
+pub(crate) use {info, warning};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::tests::{read, scratch_dir};
+
+    #[test]
+    fn rotates_the_log_file() {
+        let dir = scratch_dir("log");
+        let path = dir.join("hive.log");
+        let mut log = LogFile::open(path.clone(), 4, 2).unwrap();
+        for line in ["one\n", "two\n", "three\n", "four\n"] {
+            log.write(line).unwrap();
+        }
+        assert_eq!(read(&path).as_deref(), Some("four\n"));
+        assert_eq!(read(&snapshot_path(&path, 1)).as_deref(), Some("three\n"));
+        assert_eq!(read(&snapshot_path(&path, 2)).as_deref(), Some("two\n"));
+        assert_eq!(read(&snapshot_path(&path, 3)), None);
+
+        // A reopened log file picks up where it left off.
+        let mut log = LogFile::open(path.clone(), 10, 2).unwrap();
+        log.write("five\n").unwrap();
+        assert_eq!(read(&path).as_deref(), Some("four\nfive\n"));
+        log.write("six\n").unwrap();
+        assert_eq!(read(&path).as_deref(), Some("six\n"));
+        assert_eq!(read(&snapshot_path(&path, 1)).as_deref(), Some("four\nfive\n"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rotates_without_keeping_old_logs() {
+        let dir = scratch_dir("log-keep-none");
+        let path = dir.join("hive.log");
+        let mut log = LogFile::open(path.clone(), 1, 0).unwrap();
+        log.write("one\n").unwrap();
+        log.write("two\n").unwrap();
+        assert_eq!(read(&path).as_deref(), Some("two\n"));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
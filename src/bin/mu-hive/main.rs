@@ -1,13 +1,27 @@
-use std::io::Write;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 use clap::Parser;
 
-use mu::model::{ClusterData, ClusterUsage, MachineUsage};
-use openssh::{KnownHosts, Session};
+use mu::model::{
+    ClusterData, ClusterUsage, Inventory, MachineUsage, RunInfo, Status, Timing, Usage,
+    unix_timestamp,
+};
+use mu::protocol::{Header, Message};
 
+use crate::config::TransportKind;
+use crate::log::{info, warning};
+use crate::sessions::Sessions;
+use crate::transport::{Local, Ssh, Template, Transport};
+
+mod check;
 mod config;
+mod daemon;
+mod log;
+mod output;
+mod sessions;
+mod transport;
 
 /// Coordinate the gathering of usage information for the network of machines.
 ///
@@ -19,14 +33,60 @@ mod config;
 /// usage information for that machine. The information is serialized and sent back the connection
 /// to the `hive`. In the `hive` the information gathered by the bees from all machines is
 /// integrated and written to the output file.
+///
+/// Without a subcommand, the hive gathers the usage once and exits.
 #[derive(Debug, clap::Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    run: Option<RunArgs>,
+    #[command(flatten)]
+    limits: Limits,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Keep running, and gather the usage periodically.
+    Daemon(Box<daemon::DaemonArgs>),
+    /// Check the machines file for problems, without gathering any usage.
+    Check(check::CheckArgs),
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct RunArgs {
     /// Path to an `.ini` formatted file listing all machines for each room.
     #[clap(long, short)]
     machines: PathBuf,
     /// Path for writing the collected output `.dat` file.
     #[clap(long, short)]
     output: PathBuf,
+    /// Number of previous output files to keep around.
+    ///
+    /// The previous outputs are stored next to the output file as `<output>.1` (most recent) up to
+    /// `<output>.<keep>` (oldest).
+    #[clap(long, default_value_t = 0)]
+    keep: usize,
+    /// Path to the lock file that prevents runs from overlapping.
+    ///
+    /// If another run holds the lock, this run is skipped, and exits successfully without writing
+    /// anything. A daemon fails to start instead. Defaults to the output path with `.lock`
+    /// appended.
+    #[clap(long)]
+    lock: Option<PathBuf>,
+    /// Maximum age in seconds of usage information that is carried over from the previous output.
+    ///
+    /// When a machine cannot be reached, its last known usage is read from the previous output
+    /// file and marked as stale, until it is older than this.
+    #[clap(long, default_value_t = 300)]
+    max_staleness: u64,
+    /// Seconds after which the hardware inventory of a machine is gathered anew.
+    ///
+    /// The inventory is kept in the output file between runs. It is also gathered anew after a
+    /// machine has rebooted, and only from bees that have reported that they are able to.
+    #[clap(long, default_value_t = 86400)]
+    inventory_interval: u64,
     /// Path to the `mu-bee` executable.
     ///
     /// The path should point to the location of the `mu-bee` executable from the perspective of
@@ -40,99 +100,511 @@ struct Args {
     bee_log: Option<PathBuf>,
 }
 
+/// Limits on how long we wait for the machines.
+#[derive(Debug, Clone, clap::Args)]
+pub struct Limits {
+    /// Seconds to wait for an ssh connection to be established.
+    #[clap(long, default_value = "10", value_parser = parse_seconds)]
+    connect_timeout: Duration,
+    /// Seconds to wait for the bee to finish once connected.
+    ///
+    /// For a machine with a sampling window, the window is added to this.
+    #[clap(long, default_value = "20", value_parser = parse_seconds)]
+    command_timeout: Duration,
+    /// Number of times to retry a machine after a connection failure or timeout.
+    #[clap(long, default_value_t = 1)]
+    retries: u32,
+    /// Seconds to wait before the first retry. The wait doubles with each following retry.
+    ///
+    /// The wait never grows beyond the deadline.
+    #[clap(long, default_value = "1", value_parser = parse_seconds)]
+    retry_backoff: Duration,
+    /// Seconds after which the run is wrapped up, regardless of any unfinished machines.
+    ///
+    /// Whatever was collected up to that point is written to the output file. Machines that have
+    /// not finished are recorded as timed out.
+    #[clap(long, default_value = "50", value_parser = parse_seconds)]
+    deadline: Duration,
+}
+
+impl Limits {
+    /// Returns the limits for a bee that samples the CPU usage over `window`.
+    ///
+    /// The bee spends the window sampling before it reports anything, so the command timeout is
+    /// extended by it. Otherwise, a long window would have every attempt time out.
+    fn with_window(&self, window: Option<Duration>) -> Self {
+        let command_timeout = self.command_timeout.saturating_add(window.unwrap_or_default());
+        Self { command_timeout, ..self.clone() }
+    }
+
+    /// Returns how long to wait before the given `retry`, counting from one.
+    fn retry_wait(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.retry_backoff.saturating_mul(factor).min(self.deadline)
+    }
+}
+
+/// Longest duration that is accepted on the command line.
+///
+/// Instants derived from longer durations, such as the deadline of a run, may not be representable.
+const MAX_SECONDS: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Parses a number of seconds, which must not be negative and may be at most a year.
+pub fn parse_seconds(s: &str) -> Result<Duration, String> {
+    let secs = s.parse::<f32>().map_err(|err| err.to_string())?;
+    match Duration::try_from_secs_f32(secs) {
+        Ok(duration) if duration <= MAX_SECONDS => Ok(duration),
+        _ if secs > 0.0 => {
+            Err(format!("expected at most a year's worth of seconds, but found {s}"))
+        }
+        _ => Err(format!("expected a non-negative number of seconds, but found {s}")),
+    }
+}
+
+/// A failed attempt at gathering the usage of a machine.
+#[derive(Debug)]
+pub struct Failure {
+    pub status: Status,
+    pub error: anyhow::Error,
+}
+
+trait WithStatus<T> {
+    /// Marks an error as a [`Failure`] with the provided `status`.
+    fn with_status(self, status: Status) -> std::result::Result<T, Failure>;
+}
+
+impl<T, E: Into<anyhow::Error>> WithStatus<T> for std::result::Result<T, E> {
+    fn with_status(self, status: Status) -> std::result::Result<T, Failure> {
+        self.map_err(|error| Failure { status, error: error.into() })
+    }
+}
+
+/// Gathers the usage of a machine, retrying on connection failures and timeouts.
+///
+/// The number of attempts made is recorded in `attempts`.
+async fn gather_with_retries(
+    machine: &config::MachineDefinition,
+    bee_path: &str,
+    inventory: bool,
+    bee_log_dir: Option<PathBuf>,
+    limits: &Limits,
+    sessions: &Sessions,
+    attempts: &mut u32,
+) -> std::result::Result<Message, Failure> {
+    let hn = &machine.hostname;
+    loop {
+        *attempts += 1;
+        let result =
+            gather(machine, bee_path, inventory, bee_log_dir.clone(), limits, sessions).await;
+        match result {
+            Err(Failure { status: Status::SshFailure | Status::Timeout, error })
+                if *attempts <= limits.retries =>
+            {
+                let wait = limits.retry_wait(*attempts);
+                let secs = wait.as_secs_f32();
+                warning!("({hn}) Attempt {attempts} failed, retrying in {secs} s: {error}");
+                tokio::time::sleep(wait).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Gathers the usage of a machine, along with its hardware inventory if `inventory` is set.
 pub async fn gather(
-    machine: config::MachineDefinition,
+    machine: &config::MachineDefinition,
     bee_path: &str,
+    inventory: bool,
     bee_log_dir: Option<PathBuf>,
-) -> Result<MachineUsage> {
-    // TODO: Find out from openssh crate docs whether we want 'process-based' or 'mux-based' thing idk.
-    let session = Session::connect(&machine.hostname, KnownHosts::Strict).await?;
-    // TODO: See if it's possible to more directly stream the information to our deserializer.
+    limits: &Limits,
+    sessions: &Sessions,
+) -> std::result::Result<Message, Failure> {
     let hn = &machine.hostname;
-    eprintln!("INFO: ({hn}) Connection established. Starting bee execution.");
-    let bee = session.command(bee_path).output().await?;
-    eprintln!("INFO: ({hn}) Executed bee.");
+    let bee_path = machine.bee.as_deref().unwrap_or(bee_path);
+    let mut command = vec![bee_path.to_string()];
+    if let Some(window) = machine.window {
+        command.extend(["--window".to_string(), window.as_secs_f32().to_string()]);
+    }
+    if let Some(samples) = machine.samples {
+        command.extend(["--samples".to_string(), samples.to_string()]);
+    }
+    if inventory {
+        command.push("--inventory".to_string());
+    }
+    command.extend(machine.bee_config.clone());
+    let limits = &limits.with_window(machine.window);
+    // TODO: See if it's possible to more directly stream the information to our deserializer.
+    let bee = match &machine.transport {
+        TransportKind::Ssh => {
+            Ssh { sessions, settings: &machine.ssh }.execute(hn, &command, limits).await?
+        }
+        TransportKind::Local => Local.execute(hn, &command, limits).await?,
+        TransportKind::Command(template) => {
+            Template { template, settings: &machine.ssh }.execute(hn, &command, limits).await?
+        }
+    };
+    info!("({hn}) Executed bee.");
     if let Some(bee_log_dir) = bee_log_dir {
         let log_path = bee_log_dir.join(format!("bee-{hn}.log"));
-        std::fs::write(&log_path, bee.stderr)
-            .context(format!("could not write bee log to {log_path:?}"))?;
-        eprintln!("INFO: ({hn}) Wrote bee log to {log_path:?}.");
+        match std::fs::write(&log_path, &bee.stderr) {
+            Ok(()) => info!("({hn}) Wrote bee log to {log_path:?}."),
+            Err(e) => warning!("({hn}) Could not write bee log to {log_path:?}: {e}"),
+        }
+    }
+    if !bee.status.success() {
+        let stderr = String::from_utf8_lossy(&bee.stderr);
+        let error = match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+            Some(last_line) => {
+                anyhow::anyhow!("bee exited with {}: {}", bee.status, last_line.trim())
+            }
+            None => anyhow::anyhow!("bee exited with {}", bee.status),
+        };
+        return Err(Failure { status: Status::BeeExit(bee.status.code()), error });
+    }
+    let message = mu::protocol::read(&bee.stdout)
+        .context("could not decode output from bee")
+        .with_status(Status::Deserialization)?;
+    info!("({hn}) Deserialized info.");
+    if let Some(mismatch) = message.header.mismatch() {
+        warning!("({hn}) Version mismatch: {mismatch}.");
     }
-    let info =
-        serde_json::from_slice(&bee.stdout).context("could not deserialize output from bee")?;
-    eprintln!("INFO: ({hn}) Deserialized info.");
-    eprintln!("INFO: ({hn}) Done.");
-    Ok(MachineUsage { definition: machine.into(), usage: info })
+    info!("({hn}) Done.");
+    Ok(message)
 }
 
 pub async fn peruse(
     machines_config: config::MachineDefinitions,
-    bee_path: &str,
-    bee_log_dir: Option<PathBuf>,
+    args: &RunArgs,
+    limits: &Limits,
+    previous: Option<&ClusterData>,
+    sessions: &Sessions,
 ) -> Result<ClusterUsage> {
+    let deadline = tokio::time::Instant::now() + limits.deadline;
+    for machine in machines_config.iter().filter(|machine| !machine.enabled) {
+        info!("Skipping {:?}, which is disabled.", machine.hostname);
+    }
     let tasks = machines_config
-        .into_iter()
+        .iter()
+        .filter(|machine| machine.enabled)
         .cloned()
-        .map(|machine| {
-            let bee_path = bee_path.to_string();
-            let bee_log_dir = bee_log_dir.clone();
-            eprintln!("INFO: Setting up ssh into {:?}.", machine.hostname);
+        .map(|mut machine| {
+            let (inventory, refresh_inventory) =
+                cached_inventory(previous, &machine.hostname, args.inventory_interval);
+            if refresh_inventory {
+                info!("({}) Asking for the hardware inventory.", machine.hostname);
+            }
+            if (machine.window.is_some() || machine.samples.is_some())
+                && !can_sample(previous, &machine.hostname)
+            {
+                info!(
+                    "({}) Not asking for a sampling window, which the bee may not know of.",
+                    machine.hostname
+                );
+                machine.window = None;
+                machine.samples = None;
+            }
+            let bee_path = args.bee.clone();
+            let bee_log_dir = args.bee_log.clone();
+            let limits = limits.clone();
+            let sessions = sessions.clone();
+            info!("Setting up connection to {:?}.", machine.hostname);
             tokio::spawn(async move {
-                let hostname = machine.hostname.clone();
-                gather(machine, &bee_path, bee_log_dir)
-                    .await
-                    .context(format!("problem while gathering usage from {hostname:?}"))
+                let start = std::time::Instant::now();
+                let mut attempts = 0;
+                let gathering = gather_with_retries(
+                    &machine,
+                    &bee_path,
+                    refresh_inventory,
+                    bee_log_dir,
+                    &limits,
+                    &sessions,
+                    &mut attempts,
+                );
+                let result = match tokio::time::timeout_at(deadline, gathering).await {
+                    Ok(result) => result,
+                    Err(_) => Err(Failure {
+                        status: Status::Timeout,
+                        error: anyhow::anyhow!("the deadline of the run was reached"),
+                    }),
+                };
+                let timing = Timing { attempts, duration: start.elapsed().as_secs_f32() };
+                (machine, result, timing, inventory)
             })
         })
         .collect::<Vec<_>>();
 
     let mut usage = Vec::new();
     for task in tasks {
-        match task.await? {
-            Ok(rich_info) => usage.push(rich_info),
-            Err(e) => {
-                let root_cause = e.root_cause();
-                eprintln!("WARNING: {e}");
-                eprintln!("         {root_cause}");
+        let (machine, result, timing, inventory) = task.await?;
+        let Timing { attempts, duration } = timing;
+        info!("({}) Took {duration:.2} s over {attempts} attempt(s).", machine.hostname);
+        let machine_usage = match result {
+            Ok(Message { header, mut usage }) => MachineUsage {
+                definition: machine.into(),
+                status: Status::Ok,
+                error: None,
+                last_seen: Some(unix_timestamp()),
+                // The inventory is kept with the machine rather than with its usage, such that it
+                // outlives the usage of a machine that cannot be reached.
+                inventory: usage.inventory.take().or(inventory),
+                usage: Some(usage),
+                bee: Some(header),
+                timing: Some(timing),
+            },
+            Err(Failure { status, error }) => {
+                let hostname = &machine.hostname;
+                warning!("Problem while gathering usage from {hostname:?} ({status}): {error:#}");
+                let Remembered { last_seen, usage, bee } = previous
+                    .map(|previous| remember(previous, hostname, args.max_staleness))
+                    .unwrap_or_default();
+                if let Some(last_seen) = last_seen
+                    && usage.is_some()
+                {
+                    let age = unix_timestamp().saturating_sub(last_seen);
+                    info!("({hostname}) Carrying over usage from {age} s ago.");
+                }
+                MachineUsage {
+                    definition: machine.into(),
+                    status,
+                    error: Some(format!("{error:#}")),
+                    last_seen,
+                    inventory,
+                    usage,
+                    bee,
+                    timing: Some(timing),
+                }
             }
         };
+        usage.push(machine_usage);
     }
 
-    let nsuccess = usage.len();
-    let n = machines_config.len();
-    eprintln!("INFO: All machines have been perused. ({nsuccess}/{n} success)");
+    let nsuccess = usage.iter().filter(|machine| machine.status.is_ok()).count();
+    let n = usage.len();
+    info!("All machines have been perused. ({nsuccess}/{n} success)");
 
     // TODO: Create a from or something here. That'd be nicer.
     Ok(ClusterUsage::new(usage.into_boxed_slice()))
 }
 
-fn main() -> Result<()> {
+/// What we still know about a machine from the data written by a previous run.
+#[derive(Debug, Default)]
+struct Remembered {
+    last_seen: Option<u64>,
+    /// The last known usage, if it is not older than the maximum staleness.
+    usage: Option<Usage>,
+    bee: Option<Header>,
+}
+
+/// Looks up what the `previous` data knows about the machine with `hostname`.
+fn remember(previous: &ClusterData, hostname: &str, max_staleness: u64) -> Remembered {
+    let Some(machine) =
+        previous.usage.iter().find(|machine| machine.definition.hostname == hostname)
+    else {
+        return Remembered::default();
+    };
+    // Data written before we kept track of this only contains machines that were seen.
+    let last_seen = machine.last_seen.or(machine.status.is_ok().then_some(previous.timestamp));
+    let fresh = last_seen
+        .is_some_and(|last_seen| unix_timestamp().saturating_sub(last_seen) <= max_staleness);
+    if !fresh {
+        return Remembered { last_seen, ..Default::default() };
+    }
+    Remembered { last_seen, usage: machine.usage.clone(), bee: machine.bee.clone() }
+}
+
+/// Largest difference in seconds between two reported boot times of the same boot.
+///
+/// The kernel derives the boot time from the current time and the uptime, so it shifts a little
+/// when the clock is adjusted.
+const BOOT_TIME_TOLERANCE: u64 = 60;
+
+/// Looks up the hardware inventory of the machine with `hostname` in the `previous` data, and
+/// returns it along with whether the bee should be asked for it anew.
+///
+/// The inventory is due once it is older than `interval` seconds, or when the machine has been
+/// rebooted since it was gathered. Bees that did not report that they can gather it are never
+/// asked for it, since they would fail on the unknown argument.
+fn cached_inventory(
+    previous: Option<&ClusterData>,
+    hostname: &str,
+    interval: u64,
+) -> (Option<Inventory>, bool) {
+    let Some(machine) = previous
+        .and_then(|previous| previous.usage.iter().find(|m| m.definition.hostname == hostname))
+    else {
+        return (None, false);
+    };
+    let capable = machine.bee.as_ref().is_some_and(|bee| bee.has_capability("inventory"));
+    let due = match &machine.inventory {
+        None => true,
+        Some(inventory) => {
+            let rebooted =
+                machine.usage.as_ref().and_then(|usage| usage.boot_time).is_some_and(|boot_time| {
+                    boot_time.abs_diff(inventory.boot_time) > BOOT_TIME_TOLERANCE
+                });
+            unix_timestamp().saturating_sub(inventory.gathered) >= interval || rebooted
+        }
+    };
+    (machine.inventory.clone(), capable && due)
+}
+
+/// Returns whether the bee of the machine with `hostname` reported in the `previous` data that it
+/// can sample its usage over a window.
+///
+/// Older bees take `--window` for the path to their config file, and would quietly drop their
+/// ignore rules. They are only asked for a window once they have reported that they understand it.
+fn can_sample(previous: Option<&ClusterData>, hostname: &str) -> bool {
+    previous
+        .and_then(|previous| previous.usage.iter().find(|m| m.definition.hostname == hostname))
+        .and_then(|machine| machine.bee.as_ref())
+        .is_some_and(|bee| bee.has_capability("sampling"))
+}
+
+/// Reads the data written by a previous run, if it is there.
+fn read_previous(path: &std::path::Path) -> Option<ClusterData> {
+    let bytes = std::fs::read(path).ok()?;
+    match serde_json::from_slice(&bytes) {
+        Ok(data) => Some(data),
+        Err(e) => {
+            warning!("Could not read previous output {path:?}: {e}");
+            None
+        }
+    }
+}
+
+impl RunArgs {
+    /// Returns the path to the lock file, which defaults to the output path with `.lock` appended.
+    fn lock_path(&self) -> PathBuf {
+        self.lock.clone().unwrap_or_else(|| {
+            let mut path = self.output.clone().into_os_string();
+            path.push(".lock");
+            path.into()
+        })
+    }
+}
+
+/// Takes the exclusive lock at `path`.
+///
+/// Returns `None` if the lock is currently held by another run. The lock is released when the
+/// returned file is dropped or when this process exits.
+fn lock(path: &std::path::Path) -> Result<Option<std::fs::File>> {
+    let file = std::fs::File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .context(format!("could not open lock file {path:?}"))?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(std::fs::TryLockError::WouldBlock) => Ok(None),
+        Err(std::fs::TryLockError::Error(e)) => {
+            Err(e).context(format!("could not take lock {path:?}"))
+        }
+    }
+}
+
+/// Gathers the usage of all machines once and writes it to the output file.
+pub async fn round(args: &RunArgs, limits: &Limits, sessions: &Sessions) -> Result<()> {
     let start = std::time::Instant::now();
-    let args = Args::parse();
+    let start_timestamp = unix_timestamp();
 
+    // The machines file is read anew for every round, such that changes are picked up by a
+    // running daemon.
     let machines_path = &args.machines;
     let machines_config = config::MachineDefinitions::read_from_config(machines_path)
         .context(format!("could not process machines file {machines_path:?}"))?;
 
-    let runtime = tokio::runtime::Runtime::new().context("could not set up async runtime")?;
-    let usage =
-        runtime.block_on(async { peruse(machines_config, &args.bee, args.bee_log).await })?;
+    let previous = read_previous(&args.output);
+    let usage = peruse(machines_config, args, limits, previous.as_ref(), sessions).await?;
 
-    let data = ClusterData::new(usage);
+    let mut data = ClusterData::new(usage);
+    data.run = Some(RunInfo {
+        start: start_timestamp,
+        duration: start.elapsed().as_secs_f32(),
+        attempted: data.usage.len() as u32,
+        succeeded: data.usage.iter().filter(|machine| machine.status.is_ok()).count() as u32,
+        hive_version: env!("CARGO_PKG_VERSION").to_string(),
+    });
 
     let output_path = &args.output;
-    // We first serialize into memory before writing the file, rather than writing to the file
-    // directly, to limit the time that the file is in an invalid state.
     let output = serde_json::to_string_pretty(&data)
+        .context(format!("could not serialize collected usage for {output_path:?}"))?;
+    // The output is written to a temporary file first and then moved into place, such that a
+    // reader never observes a partially written file.
+    output::write_atomically(output_path, output.as_bytes(), args.keep)
         .context(format!("could not write collected usage to output file {output_path:?}"))?;
-    let mut output_file = std::fs::File::create(output_path)
-        .context(format!("could not open output file {output_path:?}"))?;
-    output_file.write_all(output.as_bytes())?;
     let timestamp = data.timestamp;
-    eprintln!("INFO: Output was written to {output_path:?} with timestamp {timestamp}.");
+    info!("Output was written to {output_path:?} with timestamp {timestamp}.");
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let start = std::time::Instant::now();
+    let args = Args::parse();
+    let run = match args.command {
+        Some(Command::Daemon(daemon_args)) => return daemon::run(*daemon_args),
+        Some(Command::Check(check_args)) => return check::run(check_args),
+        // Clap makes sure that the arguments for a single run are present without a subcommand.
+        None => args.run.expect("arguments for a single run are required"),
+    };
+
+    let Some(_lock) = lock(&run.lock_path())? else {
+        info!("Another run is still holding {:?}. Skipping this run.", run.lock_path());
+        return Ok(());
+    };
+
+    let runtime = tokio::runtime::Runtime::new().context("could not set up async runtime")?;
+    runtime.block_on(async {
+        let sessions = Sessions::default();
+        let result = round(&run, &args.limits, &sessions).await;
+        sessions.close().await;
+        result
+    })?;
 
     let duration = start.elapsed().as_secs_f32();
-    eprintln!("INFO: Execution took {duration:.2} s.");
+    info!("Execution took {duration:.2} s.");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> Limits {
+        Limits {
+            connect_timeout: Duration::from_secs(10),
+            command_timeout: Duration::from_secs(20),
+            retries: 1,
+            retry_backoff: Duration::from_secs(1),
+            deadline: Duration::from_secs(50),
+        }
+    }
+
+    #[test]
+    fn parses_seconds() {
+        assert_eq!(parse_seconds("0"), Ok(Duration::ZERO));
+        assert_eq!(parse_seconds("2.5"), Ok(Duration::from_millis(2500)));
+        assert_eq!(parse_seconds("31536000"), Ok(MAX_SECONDS));
+        for s in ["-1", "nan", "inf", "1e30", "4e7", "", "ten"] {
+            assert!(parse_seconds(s).is_err(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn doubles_the_wait_up_to_the_deadline() {
+        let limits = limits();
+        let waits = (1..=7).map(|retry| limits.retry_wait(retry).as_secs()).collect::<Vec<_>>();
+        assert_eq!(waits, [1, 2, 4, 8, 16, 32, 50]);
+        assert_eq!(limits.retry_wait(u32::MAX), limits.deadline);
+    }
+
+    #[test]
+    fn extends_the_command_timeout_by_the_window() {
+        let limits = limits();
+        let window = Duration::from_secs(5);
+        assert_eq!(limits.with_window(Some(window)).command_timeout, Duration::from_secs(25));
+        assert_eq!(limits.with_window(None).command_timeout, Duration::from_secs(20));
+    }
+}
@@ -0,0 +1,158 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+/// Writes `contents` to `path` without ever leaving a partially written file at `path`.
+///
+/// The contents are first written to a temporary file in the same directory, synced to disk, and
+/// then renamed into place. Readers will either see the previous file or the new one.
+///
+/// If `keep` is larger than zero, the previous `keep` versions of the file are retained as
+/// `path.1` (most recent) through `path.{keep}` (oldest).
+pub fn write_atomically(path: &Path, contents: &[u8], keep: usize) -> Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name().context(format!("{path:?} is not a file path"))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(format!(".tmp.{}", std::process::id()));
+    let tmp_path = dir.join(tmp_name);
+
+    let result =
+        write_synced(&tmp_path, contents).and_then(|_| rotate(path, keep)).and_then(|_| {
+            std::fs::rename(&tmp_path, path)
+                .context(format!("could not move {tmp_path:?} into place at {path:?}"))
+        });
+    if result.is_err() {
+        // Clean up after ourselves, but the original error is what matters.
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result?;
+
+    // Make sure the rename itself is persisted.
+    std::fs::File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .context(format!("could not sync directory {dir:?}"))
+}
+
+fn write_synced(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut file =
+        std::fs::File::create(path).context(format!("could not create temporary file {path:?}"))?;
+    file.write_all(contents).context(format!("could not write to temporary file {path:?}"))?;
+    file.sync_all().context(format!("could not sync temporary file {path:?}"))
+}
+
+/// Returns the path of the `n`th snapshot of `path`.
+pub fn snapshot_path(path: &Path, n: usize) -> PathBuf {
+    let mut snapshot = path.as_os_str().to_owned();
+    snapshot.push(format!(".{n}"));
+    PathBuf::from(snapshot)
+}
+
+/// Shifts the existing snapshots of `path` up by one, such that the first snapshot is free to be
+/// taken anew.
+///
+/// The `keep`th snapshot is replaced by the one before it, so no more than `keep` are retained.
+pub fn shift_snapshots(path: &Path, keep: usize) -> Result<()> {
+    for n in (1..keep).rev() {
+        let from = snapshot_path(path, n);
+        if from.exists() {
+            let to = snapshot_path(path, n + 1);
+            std::fs::rename(&from, &to).context(format!("could not move {from:?} to {to:?}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Shifts the existing snapshots of `path` up by one, and stores the current `path` as the first
+/// snapshot.
+///
+/// The current file stays in place, such that it can be replaced atomically afterwards.
+fn rotate(path: &Path, keep: usize) -> Result<()> {
+    if keep == 0 || !path.exists() {
+        return Ok(());
+    }
+    shift_snapshots(path, keep)?;
+
+    let first = snapshot_path(path, 1);
+    // A hard link is cheap and leaves the current file untouched. Not every file system supports
+    // them, so we fall back to copying.
+    let _ = std::fs::remove_file(&first);
+    if std::fs::hard_link(path, &first).is_err() {
+        std::fs::copy(path, &first).context(format!("could not copy {path:?} to {first:?}"))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Creates an empty directory for a single test.
+    pub fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mu-hive-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Reads the file at `path`, or `None` if there is no such file.
+    pub fn read(path: &Path) -> Option<String> {
+        std::fs::read_to_string(path).ok()
+    }
+
+    #[test]
+    fn keeps_the_most_recent_snapshots() {
+        let dir = scratch_dir("snapshots");
+        let path = dir.join("mu.dat");
+        for n in 1..=5 {
+            write_atomically(&path, n.to_string().as_bytes(), 3).unwrap();
+        }
+        assert_eq!(read(&path).as_deref(), Some("5"));
+        assert_eq!(read(&snapshot_path(&path, 1)).as_deref(), Some("4"));
+        assert_eq!(read(&snapshot_path(&path, 2)).as_deref(), Some("3"));
+        assert_eq!(read(&snapshot_path(&path, 3)).as_deref(), Some("2"));
+        assert_eq!(read(&snapshot_path(&path, 4)), None);
+        // Nothing else is left behind, such as temporary files.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 4);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn keeps_no_snapshots_for_zero() {
+        let dir = scratch_dir("no-snapshots");
+        let path = dir.join("mu.dat");
+        write_atomically(&path, b"1", 0).unwrap();
+        write_atomically(&path, b"2", 0).unwrap();
+        assert_eq!(read(&path).as_deref(), Some("2"));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn leaves_the_target_intact_when_writing_fails() {
+        let dir = scratch_dir("failed-write");
+        let path = dir.join("mu.dat");
+        write_atomically(&path, b"1", 2).unwrap();
+        write_atomically(&path, b"2", 2).unwrap();
+
+        // The temporary file cannot be created where a directory is in the way.
+        let tmp = dir.join(format!(".mu.dat.tmp.{}", std::process::id()));
+        std::fs::create_dir(&tmp).unwrap();
+        assert!(write_atomically(&path, b"3", 2).is_err());
+        std::fs::remove_dir(&tmp).unwrap();
+        assert_eq!(read(&path).as_deref(), Some("2"));
+
+        // Nor can a snapshot be moved onto a directory.
+        std::fs::create_dir_all(snapshot_path(&path, 2).join("occupied")).unwrap();
+        assert!(write_atomically(&path, b"3", 2).is_err());
+        assert_eq!(read(&path).as_deref(), Some("2"));
+        assert_eq!(read(&snapshot_path(&path, 1)).as_deref(), Some("1"));
+        assert!(!tmp.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Context;
+use openssh::{KnownHosts, Session, SessionBuilder};
+
+use mu::model::Status;
+
+use crate::config::SshSettings;
+use crate::log::{info, warning};
+use crate::{Failure, WithStatus};
+
+/// Open ssh sessions, shared between rounds of gathering.
+///
+/// Reusing a session saves us the ssh handshake for every round. A session that turns out to be
+/// broken, or that was set up with different settings, is replaced by a new one.
+#[derive(Debug, Clone, Default)]
+pub struct Sessions(Arc<Mutex<HashMap<String, Entry>>>);
+
+/// An open session, along with the settings it was set up with.
+type Entry = (SshSettings, Arc<Session>);
+
+impl Sessions {
+    /// Returns an open session to `hostname`, connecting to it with `settings` if necessary.
+    pub async fn get(
+        &self,
+        hostname: &str,
+        settings: &SshSettings,
+        connect_timeout: Duration,
+    ) -> Result<Arc<Session>, Failure> {
+        let cached = self.0.lock().unwrap().get(hostname).cloned();
+        if let Some((cached_settings, _)) = &cached
+            && cached_settings != settings
+        {
+            info!("({hostname}) Connection settings have changed. Reconnecting.");
+            self.forget(hostname);
+        } else if let Some((_, session)) = cached {
+            match tokio::time::timeout(connect_timeout, session.check()).await {
+                Ok(Ok(())) => return Ok(session),
+                _ => {
+                    info!("({hostname}) Cached session is broken. Reconnecting.");
+                    self.forget(hostname);
+                }
+            }
+        }
+
+        // TODO: Find out from openssh crate docs whether we want 'process-based' or 'mux-based' thing idk.
+        let mut builder = SessionBuilder::default();
+        builder.known_hosts_check(KnownHosts::Strict);
+        let SshSettings { user, port, identity, jump } = settings;
+        if let Some(user) = user {
+            builder.user(user.clone());
+        }
+        if let Some(port) = port {
+            builder.port(*port);
+        }
+        if let Some(identity) = identity {
+            builder.keyfile(identity);
+        }
+        if let Some(jump) = jump {
+            builder.jump_hosts([jump]);
+        }
+        let connect = builder.connect(hostname);
+        let session = tokio::time::timeout(connect_timeout, connect)
+            .await
+            .context(format!("could not connect within {connect_timeout:?}"))
+            .with_status(Status::Timeout)?
+            .with_status(Status::SshFailure)?;
+        let session = Arc::new(session);
+        let entry = (settings.clone(), Arc::clone(&session));
+        self.0.lock().unwrap().insert(hostname.to_string(), entry);
+        Ok(session)
+    }
+
+    /// Drops the session to `hostname`, such that the next [`Sessions::get`] reconnects.
+    pub fn forget(&self, hostname: &str) {
+        self.0.lock().unwrap().remove(hostname);
+    }
+
+    /// Closes all sessions that are not in use anymore.
+    pub async fn close(&self) {
+        let sessions = std::mem::take(&mut *self.0.lock().unwrap());
+        for (hostname, (_, session)) in sessions {
+            let Ok(session) = Arc::try_unwrap(session) else { continue };
+            if let Err(e) = session.close().await {
+                warning!("({hostname}) Could not close session: {e}");
+            }
+        }
+    }
+}
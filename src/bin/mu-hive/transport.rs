@@ -0,0 +1,179 @@
+//! The ways in which the hive can reach the bee on a machine.
+
+use std::process::Output;
+
+use anyhow::Context;
+
+use mu::model::Status;
+
+use crate::config::SshSettings;
+use crate::log::info;
+use crate::sessions::Sessions;
+use crate::{Failure, Limits, WithStatus};
+
+/// Runs the bee on a machine.
+pub trait Transport {
+    /// Runs `command` on the machine with `hostname` and collects its output.
+    ///
+    /// The first element of `command` is the path to the bee, and the remaining elements are its
+    /// arguments.
+    fn execute(
+        &self,
+        hostname: &str,
+        command: &[String],
+        limits: &Limits,
+    ) -> impl Future<Output = Result<Output, Failure>> + Send;
+}
+
+/// Reaches the bee over ssh, reusing any open session to the machine.
+pub struct Ssh<'s> {
+    pub sessions: &'s Sessions,
+    pub settings: &'s SshSettings,
+}
+
+impl Transport for Ssh<'_> {
+    async fn execute(
+        &self,
+        hostname: &str,
+        command: &[String],
+        limits: &Limits,
+    ) -> Result<Output, Failure> {
+        let Limits { connect_timeout, command_timeout, .. } = *limits;
+
+        let session = self.sessions.get(hostname, self.settings, connect_timeout).await?;
+        info!("({hostname}) Connection established. Starting bee execution.");
+        let (program, args) = command.split_first().expect("command must contain the bee");
+        let mut remote = session.command(program);
+        remote.args(args);
+        let output = tokio::time::timeout(command_timeout, remote.output())
+            .await
+            .context(format!("bee did not finish within {command_timeout:?}"))
+            .with_status(Status::Timeout)
+            .and_then(|output| output.with_status(Status::SshFailure));
+        if output.is_err() {
+            // Whatever happened, this session cannot be trusted with the next attempt.
+            self.sessions.forget(hostname);
+        }
+        output
+    }
+}
+
+/// Runs the bee directly on the machine running the hive.
+pub struct Local;
+
+impl Transport for Local {
+    async fn execute(
+        &self,
+        _hostname: &str,
+        command: &[String],
+        limits: &Limits,
+    ) -> Result<Output, Failure> {
+        let (program, args) = command.split_first().expect("command must contain the bee");
+        let mut local = tokio::process::Command::new(program);
+        local.args(args);
+        run_local(local, limits).await
+    }
+}
+
+/// Runs a command template through the local shell.
+///
+/// The following placeholders are replaced in the template:
+///
+/// - `{host}`: the hostname of the machine,
+/// - `{user}`, `{port}`: the `user` and `port` attributes of the machine,
+/// - `{bee}`: the bee command, including its arguments, quoted for the shell.
+///
+/// For example, `ssh -p {port} {user}@{host} {bee}` or `docker exec {host} {bee}`. Templates are
+/// checked when the machines file is read, so each placeholder has a value by the time it runs.
+pub struct Template<'t> {
+    pub template: &'t str,
+    pub settings: &'t SshSettings,
+}
+
+/// Names of the placeholders that can be used in a command template.
+pub const PLACEHOLDERS: &[&str] = &["host", "user", "port", "bee"];
+
+/// Returns the names of the placeholders in `template`, such as `host` for `{host}`.
+///
+/// Only words in braces count as placeholders, such that shell constructs like `{ a; b; }` can be
+/// used in a template.
+pub fn placeholders(template: &str) -> impl Iterator<Item = &str> {
+    template.split('{').skip(1).filter_map(|s| {
+        let (name, _) = s.split_once('}')?;
+        let word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+        (!name.is_empty() && name.chars().all(word)).then_some(name)
+    })
+}
+
+impl Template<'_> {
+    /// Fills in the placeholders of the template. Anything else in braces is left as is.
+    fn expand(&self, hostname: &str, bee: &str) -> String {
+        let value = |name: &str| match name {
+            "host" => Some(hostname.to_string()),
+            "user" => self.settings.user.clone(),
+            "port" => self.settings.port.map(|port| port.to_string()),
+            "bee" => Some(bee.to_string()),
+            _ => None,
+        };
+        let mut expanded = String::new();
+        let mut rest = self.template;
+        while let Some((before, after)) = rest.split_once('{') {
+            expanded.push_str(before);
+            let replaced =
+                after.split_once('}').and_then(|(name, tail)| Some((value(name)?, tail)));
+            match replaced {
+                Some((value, tail)) => {
+                    expanded.push_str(&value);
+                    rest = tail;
+                }
+                None => {
+                    expanded.push('{');
+                    rest = after;
+                }
+            }
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+}
+
+impl Transport for Template<'_> {
+    async fn execute(
+        &self,
+        hostname: &str,
+        command: &[String],
+        limits: &Limits,
+    ) -> Result<Output, Failure> {
+        let bee = command.iter().map(|arg| shell_quote(arg)).collect::<Vec<_>>().join(" ");
+        let expanded = self.expand(hostname, &bee);
+        info!("({hostname}) Running {expanded:?}.");
+        let mut shell = tokio::process::Command::new("sh");
+        shell.arg("-c").arg(expanded);
+        run_local(shell, limits).await
+    }
+}
+
+async fn run_local(
+    mut command: tokio::process::Command,
+    limits: &Limits,
+) -> Result<Output, Failure> {
+    let command_timeout = limits.command_timeout;
+    // If we give up on the command, we don't want it to linger.
+    command.kill_on_drop(true);
+    tokio::time::timeout(command_timeout, command.output())
+        .await
+        .context(format!("command did not finish within {command_timeout:?}"))
+        .with_status(Status::Timeout)?
+        .context("could not execute command")
+        .with_status(Status::SshFailure)
+}
+
+/// Quotes `s` such that the shell will treat it as a single word.
+fn shell_quote(s: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !s.is_empty() && s.chars().all(safe) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', r#"'\''"#))
+    }
+}
@@ -5,14 +5,19 @@ use anyhow::{Context, Result};
 use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use ratatui::layout::{Constraint, Layout};
 use ratatui::prelude::{Buffer, Rect};
-use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::style::{Color, Modifier, Style, Stylize};
 use ratatui::text::{Line, Span, Text};
-use ratatui::widgets::{Block, Cell, LineGauge, Paragraph, Row, Table, Widget, Wrap};
+use ratatui::widgets::{Block, Cell, LineGauge, Paragraph, Row, Table, TableState, Widget, Wrap};
 use ratatui::{DefaultTerminal, Frame, symbols};
 
 use crate::config::{Colors, Config};
-use crate::view::{ClusterDataView, MachineView};
-use mu::model::{ActiveUser, ClusterData, CpuUsage, HostInfo, Memory, Owner};
+use crate::view::{
+    ClusterDataView, DISK_WARNING_FRACTION, MachineView, THRASHING_PRESSURE_PERCENT,
+};
+use mu::model::{
+    ActiveUser, ClusterData, CpuUsage, HostInfo, MountHealth, Owner, Pressure, RunInfo, Sampling,
+    Session, SessionKind, Thresholds,
+};
 
 pub struct App {
     colors: Colors,
@@ -23,6 +28,13 @@ pub struct App {
     /// Report if the data was refreshed successfully.
     success: bool,
     show_room: bool,
+    show_temperature: bool,
+    /// Hostname of the selected machine, if any.
+    selected: Option<String>,
+    /// Whether the details of the selected machine are shown in place of the table.
+    detail: bool,
+    /// Whether the hardware inventory of the machines is shown in place of the table.
+    inventory: bool,
     #[allow(dead_code)] // TODO
     dirty: bool,
     exit: bool,
@@ -38,6 +50,20 @@ fn log(host_info: &HostInfo) -> Result<()> {
     Ok(())
 }
 
+/// Formats a duration compactly, with at most two units, such as `42s`, `5m`, or `3d 4h`.
+fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, mins) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+    match (days, hours, mins) {
+        (0, 0, 0) => format!("{secs}s"),
+        (0, 0, m) => format!("{m}m"),
+        (0, h, 0) => format!("{h}h"),
+        (0, h, m) => format!("{h}h {m}m"),
+        (d, 0, _) => format!("{d}d"),
+        (d, h, _) => format!("{d}d {h}h"),
+    }
+}
+
 impl App {
     pub fn new(config: Config) -> Result<Self> {
         let host_info = HostInfo::new()?;
@@ -51,6 +77,10 @@ impl App {
             access_logged,
             success: false,
             show_room: config.show_room,
+            show_temperature: config.show_temperature,
+            selected: None,
+            detail: false,
+            inventory: false,
             dirty: true,
             exit: false,
         })
@@ -120,13 +150,43 @@ impl App {
             KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
                 self.exit()
             }
-            KeyCode::Char('j') | KeyCode::Down => {}
-            KeyCode::Char('k') | KeyCode::Up => {}
+            KeyCode::Char('j') | KeyCode::Down => self.select(1),
+            KeyCode::Char('k') | KeyCode::Up => self.select(-1),
+            KeyCode::Enter if self.selected.is_some() && !self.inventory => {
+                self.detail = !self.detail
+            }
+            KeyCode::Char('i') => self.inventory = !self.inventory,
+            KeyCode::Esc if self.inventory => self.inventory = false,
+            KeyCode::Esc if self.detail => self.detail = false,
+            KeyCode::Esc => self.selected = None,
             KeyCode::Char('R') => self.show_room = !self.show_room,
+            KeyCode::Char('T') => self.show_temperature = !self.show_temperature,
             _ => {}
         }
     }
 
+    /// Moves the selection `step` machines down the table, or up for a negative `step`.
+    fn select(&mut self, step: isize) {
+        let Some(data) = self.data() else { return };
+        // The machines are listed by their hostnames.
+        let mut hostnames =
+            data.usage.iter().map(|machine| &machine.definition.hostname).collect::<Vec<_>>();
+        hostnames.sort();
+        if hostnames.is_empty() {
+            return;
+        }
+        let current = self
+            .selected
+            .as_ref()
+            .and_then(|selected| hostnames.iter().position(|&hostname| hostname == selected));
+        let next = match current {
+            Some(current) => current.saturating_add_signed(step).min(hostnames.len() - 1),
+            None if step < 0 => hostnames.len() - 1,
+            None => 0,
+        };
+        self.selected = Some(hostnames[next].clone());
+    }
+
     fn exit(&mut self) {
         self.exit = true;
     }
@@ -140,6 +200,7 @@ impl App {
             self.access_logged,
             self.success,
             self.show_room,
+            self.show_temperature,
         )
     }
 }
@@ -172,8 +233,16 @@ impl Widget for &App {
             .block(Block::new());
 
         let info = Paragraph::new(header_info).wrap(Wrap { trim: true });
-        let legend_row = Row::new(["", "", "Room", "CPU", "Mem", "Active process"].map(Cell::from))
-            .fg(colors.legend);
+        let legend_row = Row::new(
+            ["", "", "Room", "CPU", "Mem", "GPU", "Disk", "Temp", "Active process"].map(Cell::from),
+        )
+        .fg(colors.legend);
+        let selected = self.selected.as_ref().and_then(|selected| {
+            view.machines.iter().position(|machine| &machine.hostname == selected)
+        });
+        let detail =
+            selected.filter(|_| self.detail).map(|idx| detail(&view.machines[idx], colors));
+        let inventory = self.inventory.then(|| inventory(&view.machines, colors));
         let machines_rows: Vec<Row> = std::iter::once(legend_row)
             .chain(view.machines.into_iter().map(|machine| IntoRow::into_row(machine, colors)))
             .collect();
@@ -185,10 +254,14 @@ impl Widget for &App {
                 Constraint::Max(23), // Note (owner).
                 if self.show_room { Constraint::Max(9) } else { Constraint::Length(0) }, // Room.
                 Constraint::Length(7), // Cores.
-                Constraint::Length(10), // Memory.
+                Constraint::Length(7), // Memory.
+                Constraint::Max(8),  // GPUs.
+                Constraint::Length(7), // Disk.
+                if self.show_temperature { Constraint::Length(5) } else { Constraint::Length(0) }, // Temperature.
                 Constraint::Max(30), // Active user.
             ],
         )
+        .row_highlight_style(Modifier::REVERSED)
         .block(Block::new());
 
         let stats_rows = view
@@ -216,14 +289,28 @@ impl Widget for &App {
             Err(error) => format!("{:.3} s in the future", error.duration().as_secs_f32()),
         };
         let notes_block = Block::bordered().title("Notes").fg(colors.notes);
-        let notes = Paragraph::new(vec![
-            Line::from("Last update:"),
-            Line::from(format!("  {age}.")),
+        let mut notes_lines = vec![Line::from("Last update:"), Line::from(format!("  {age}."))];
+        if let Some(RunInfo { duration, attempted, succeeded, hive_version, .. }) = view.notes.run {
+            // Report the health of the hive run that produced this data.
+            let health = Line::from(format!("  {succeeded}/{attempted} ok, {duration:.1}s"));
+            notes_lines.extend([
+                Line::from(format!("Hive {hive_version}:")),
+                if succeeded < attempted { health.fg(colors.unavailable) } else { health },
+            ]);
+        }
+        if !view.notes.full_mounts.is_empty() {
+            notes_lines.push(Line::from("Nearly full:").fg(colors.warning));
+            for (path, fraction) in &view.notes.full_mounts {
+                let percent = fraction * 100.0;
+                notes_lines.push(Line::from(format!("  {path} {percent:.0}%")).fg(colors.warning));
+            }
+        }
+        notes_lines.extend([
             Line::from(if view.notes.success { ":)" } else { ":(" }),
             Line::from(if view.notes.logged { "Logged." } else { "Not logged." }),
-        ])
-        .wrap(Wrap { trim: false })
-        .block(notes_block);
+        ]);
+        let notes_height = notes_lines.len() as u16 + 2;
+        let notes = Paragraph::new(notes_lines).wrap(Wrap { trim: false }).block(notes_block);
 
         let vertical_layout = Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]);
         let header_layout = Layout::horizontal([
@@ -234,7 +321,7 @@ impl Widget for &App {
         let main_layout = Layout::horizontal([Constraint::Fill(1), Constraint::Length(18)]);
         let gutter_layout = Layout::vertical([
             Constraint::Max(stats_height),
-            Constraint::Max(6),
+            Constraint::Max(notes_height),
             Constraint::Fill(1),
         ]);
         let [header_area, main_area] = vertical_layout.areas(area);
@@ -245,7 +332,17 @@ impl Widget for &App {
         info.render(info_area, buf);
         time.render(time_area, buf);
         gauge.render(gauge_area, buf);
-        machines.render(table_area, buf);
+        // The legend takes up the first row of the machines and the inventory tables.
+        let mut state = TableState::default().with_selected(selected.map(|idx| idx + 1));
+        match (inventory, detail) {
+            (Some(inventory), _) => {
+                ratatui::widgets::StatefulWidget::render(inventory, table_area, buf, &mut state)
+            }
+            (None, Some(detail)) => detail.render(table_area, buf),
+            (None, None) => {
+                ratatui::widgets::StatefulWidget::render(machines, table_area, buf, &mut state)
+            }
+        }
         stats.render(stats_area, buf);
         notes.render(notes_area, buf);
     }
@@ -258,8 +355,25 @@ trait IntoRow<'a> {
 impl<'a> IntoRow<'a> for MachineView {
     fn into_row(self, colors: &Colors) -> Row<'a> {
         let CpuUsage { used, total } = self.cpu_usage;
+        // A stale machine still has its last known usage, which we show in a subdued manner.
+        let available = self.status.is_ok() || self.stale;
+        // Processes stuck on a hung mount drive up the load, so the load says little about use.
+        let hung = self.troubled_mounts.iter().any(|&(_, health)| health == MountHealth::Hung);
+        let thrashing = self.is_thrashing();
+        let memory_pressure = self.memory_pressure();
+        // A hot or throttled machine runs slower than its busy cores suggest.
+        let overheated = self.hot || self.throttled;
+        let presence = self.at_console().map(|session| presence(session, colors));
 
-        let hostname = {
+        let hostname = if !available {
+            Cell::from(Span::from(self.hostname).fg(colors.unavailable))
+        } else if self.stale {
+            Cell::from(Span::from(self.hostname).fg(colors.stale))
+        } else if hung {
+            Cell::from(Span::from(self.hostname).fg(colors.warning).bold().reversed())
+        } else if overheated {
+            Cell::from(Span::from(self.hostname).fg(colors.warning).bold())
+        } else {
             let text = Span::from(self.hostname);
             let modifier =
                 if used == total { Modifier::BOLD | Modifier::ITALIC } else { Modifier::empty() };
@@ -288,27 +402,33 @@ impl<'a> IntoRow<'a> for MachineView {
             _ => Modifier::empty(),
         };
         let owner_name_style = Style::new().bold().add_modifier(uses_own);
-        let owner = match self.owner {
-            Owner::Member(name) => Cell::from(Line::from(vec![
+        let mut owner = match self.owner {
+            Owner::Member(name) => Line::from(vec![
                 Span::raw("  "),
                 Span::raw(name).style(owner_name_style).fg(colors.owner),
-            ])),
-            Owner::Visitor(name) => Cell::from(Line::from(vec![
+            ]),
+            Owner::Visitor(name) => Line::from(vec![
                 Span::raw("v").italic().fg(colors.visitor).add_modifier(other_user),
                 Span::raw(" "),
                 Span::raw(name).style(owner_name_style).fg(colors.owner),
-            ])),
-            Owner::Student(name) => Cell::from(Line::from(vec![
+            ]),
+            Owner::Student(name) => Line::from(vec![
                 Span::raw("s").italic().fg(colors.student).add_modifier(other_user),
                 Span::raw(" "),
                 Span::raw(name).style(owner_name_style).fg(colors.owner),
-            ])),
+            ]),
             Owner::Reserve => {
-                Cell::from(Span::raw("Reservation required").italic().fg(colors.reservation))
+                Line::from(Span::raw("Reservation required").italic().fg(colors.reservation))
             }
-            Owner::None => Cell::default(),
+            Owner::None => Line::default(),
         };
-        let cpu = {
+        if !self.tags.is_empty() {
+            owner.push_span(Span::raw(format!(" {}", self.tags.join(","))).fg(colors.room).dim());
+        }
+        let owner = Cell::from(owner);
+        let cpu = if !available {
+            Cell::default()
+        } else {
             let u = self.load_avg.one.round() as u32;
             Cell::from(Line::from(vec![
                 Span::raw(format!("{u:>3}")).fg(colors.cores_active).bold(),
@@ -317,18 +437,79 @@ impl<'a> IntoRow<'a> for MachineView {
             ]))
             .bg(colors.cores_bg)
         };
-        let mem = {
-            let Memory { used, total } = self.mem_usage;
-            let length = 5;
-            let nfilled = ((used * length) / total) as usize;
-            let filled = symbols::line::THICK_HORIZONTAL.repeat(nfilled);
-            let empty = symbols::line::HORIZONTAL.repeat(length as usize - nfilled);
+        let mem = if !available {
+            Cell::default()
+        } else if thrashing {
+            Cell::from(bar(self.mem_usage.fraction_used(), colors.warning)).bold()
+        } else if let Some(pressure) = memory_pressure {
+            // The used memory alone does not tell whether the machine is short on it, but the time
+            // spent waiting for memory does.
+            let fraction = (pressure / THRASHING_PRESSURE_PERCENT) as f64;
+            Cell::from(bar(self.mem_usage.fraction_used(), colors.pick_gradient_color(fraction)))
+        } else {
+            Cell::from(bar(self.mem_usage.fraction_used(), colors.divider))
+        };
+        let gpu = if !available {
+            Cell::default()
+        } else {
+            // Each GPU gets a single block, which grows with its utilization.
+            let blocks = self.gpus.iter().map(|gpu| match gpu.utilization {
+                Some(utilization) => {
+                    const BLOCKS: [&str; 8] = ["▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
+                    let fraction = utilization as f64 / 100.0;
+                    let level = ((fraction * 7.0).round() as usize).min(7);
+                    Span::raw(BLOCKS[level]).fg(colors.pick_gradient_color(fraction))
+                }
+                None => Span::raw("?").fg(colors.divider).dim(),
+            });
+            Cell::from(Line::from(blocks.collect::<Vec<_>>()))
+        };
+        let disk = match self.disk_usage {
+            Some(space) if available => {
+                let fraction = space.fraction_used();
+                let color =
+                    if fraction >= DISK_WARNING_FRACTION { colors.warning } else { colors.divider };
+                Cell::from(bar(fraction, color))
+            }
+            _ => Cell::default(),
+        };
+        let temperature = if !(available && self.show_temperature) {
+            Cell::default()
+        } else {
+            let color = if overheated { colors.warning } else { colors.divider };
+            let mut line = Line::from(match self.temperature {
+                Some(temperature) => Span::raw(format!("{temperature:>3.0}°")).fg(color),
+                None => Span::raw(""),
+            });
+            if self.throttled {
+                // Throttling shows even without a temperature sensor.
+                line.push_span(Span::raw("↓").fg(colors.warning).bold());
+            }
+            Cell::from(line)
+        };
+        let active_user = if !available {
+            // In place of the active user, we report why this machine is unavailable.
+            let seen = match self.last_seen.and_then(|last_seen| last_seen.elapsed().ok()) {
+                Some(age) => format!("seen {} ago", format_duration(age)),
+                None => "never seen".to_string(),
+            };
             Cell::from(Line::from(vec![
-                Span::raw(filled).fg(colors.divider),
-                Span::raw(empty).fg(colors.divider).dim(),
+                Span::raw(self.status.to_string()).italic().fg(colors.unavailable),
+                Span::raw(format!(", {seen}")).fg(colors.unavailable),
             ]))
-        };
-        let active_user = if let Some(ActiveUser { user, cores, task }) = self.active_user {
+        } else if let Some((path, health)) = self.troubled_mounts.first() {
+            // In place of the active user, we report the mounts that are in trouble.
+            let color = if hung { colors.warning } else { colors.stale };
+            let mut line = Line::from(vec![
+                Span::raw(path.clone()).bold().fg(color),
+                Span::raw(format!(" {health}")).italic().fg(color),
+            ]);
+            if self.troubled_mounts.len() > 1 {
+                let more = self.troubled_mounts.len() - 1;
+                line.push_span(Span::raw(format!(" (+{more} more)")).fg(color).dim());
+            }
+            Cell::from(line)
+        } else if let Some(ActiveUser { user, cores, gpus, task, runtime }) = self.active_user {
             let mut line = Line::from(vec![
                 Span::raw(format!("{user:>8}")).bold().fg(colors.active_user),
                 Span::raw(":").fg(colors.divider).dim(),
@@ -340,7 +521,32 @@ impl<'a> IntoRow<'a> for MachineView {
                     Span::raw(cores.to_string()).bold().fg(colors.active_cores),
                 ]);
             }
+            if gpus > 0 {
+                line.extend([
+                    Span::raw("+").fg(colors.divider).dim(),
+                    Span::raw(format!("{gpus}g")).bold().fg(colors.active_cores),
+                ]);
+            }
+            if let Some(runtime) = runtime {
+                line.extend([
+                    Span::raw(", ").fg(colors.divider).dim(),
+                    Span::raw(format_duration(runtime)).fg(colors.active_task),
+                ]);
+            }
+            if self.stale {
+                line.push_span(stale_marker(self.last_seen, colors));
+            }
+            Cell::from(line)
+        } else if let Some(presence) = presence {
+            // Without an active task, somebody at the console is the one using the machine. The
+            // sessions are listed in the detail view either way.
+            let mut line = Line::from(presence);
+            if self.stale {
+                line.push_span(stale_marker(self.last_seen, colors));
+            }
             Cell::from(line)
+        } else if self.stale {
+            Cell::from(Line::from(stale_marker(self.last_seen, colors)))
         } else {
             Cell::default() // If there is no active user process we leave the cell empty.
         };
@@ -355,7 +561,342 @@ impl<'a> IntoRow<'a> for MachineView {
             },
             cpu,
             mem,
+            gpu,
+            disk,
+            temperature,
             active_user,
         ])
+        .add_modifier(match (available, self.stale) {
+            (false, _) => Modifier::DIM,
+            (true, true) => Modifier::ITALIC,
+            (true, false) => Modifier::empty(),
+        })
+    }
+}
+
+/// Tells who is sitting at the machine and for how long they have not touched it, such as
+/// `marieke at console, idle 2m`.
+fn presence<'a>(session: &Session, colors: &Colors) -> Vec<Span<'a>> {
+    let mut spans = vec![
+        Span::raw(session.user.clone()).fg(colors.active_user),
+        Span::raw(" at console").fg(colors.divider),
+    ];
+    spans.extend(describe_idle(session).map(|idle| Span::raw(idle).fg(colors.divider)));
+    spans
+}
+
+/// Shortest idle time of a session that is worth mentioning.
+const IDLE_NOTICE: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Describes how long a session has been idle, such as `, idle 2m`, unless its user is active.
+fn describe_idle(session: &Session) -> Option<String> {
+    let idle = session.idle().filter(|&idle| idle >= IDLE_NOTICE)?;
+    Some(format!(", idle {}", format_duration(idle)))
+}
+
+/// Formats a number of bytes compactly, such as `512K`, `3.2G`, or `200G`.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    let unit = UNITS[unit];
+    if value < 10.0 && unit != "B" {
+        format!("{value:.1}{unit}")
+    } else {
+        format!("{value:.0}{unit}")
+    }
+}
+
+/// Formats a throughput in bytes per second, leaving it blank if unknown.
+fn format_rate(rate: Option<u64>) -> String {
+    rate.map(format_bytes).unwrap_or_default()
+}
+
+/// Lists the processes, GPUs, disks, network interfaces, and login sessions of a single machine.
+///
+/// The throughput of processes and disks is read into `In` and written from `Out`, and that of
+/// network interfaces is received into `In` and transmitted from `Out`.
+fn detail<'a>(machine: &MachineView, colors: &Colors) -> Table<'a> {
+    let mut processes = machine.processes.to_vec();
+    processes.sort_by(|a, b| b.usage.total_cmp(&a.usage));
+
+    let legend = Row::new([
+        "PID", "User", "CPU", "Peak", "RSS", "Virt", "Thr", "Time", "In/s", "Out/s", "Command",
+    ])
+    .fg(colors.legend);
+    // The jobs are listed by the pid of the process that started them.
+    let job_rows = machine.jobs.iter().map(|job| {
+        let command = job.command.clone().unwrap_or_else(|| job.name.clone());
+        let members = if job.processes == 1 { "process" } else { "processes" };
+        Row::new(vec![
+            Cell::from(job.pgid.to_string()).fg(colors.legend),
+            Cell::from(job.user.clone()).bold().fg(colors.active_user),
+            Cell::from(format!("{:.0}%", job.usage)).fg(colors.active_cores),
+            Cell::default(),
+            Cell::from(format_bytes(job.rss)),
+            Cell::default(),
+            Cell::default(),
+            Cell::from(job.runtime().map(format_duration).unwrap_or_default()),
+            Cell::default(),
+            Cell::default(),
+            Cell::from(Line::from(vec![
+                Span::raw(command).italic().fg(colors.active_task),
+                Span::raw(format!(" · job of {} {members}", job.processes)).fg(colors.divider),
+            ])),
+        ])
+    });
+    let process_rows = processes.into_iter().map(|proc| {
+        let command = proc.command.clone().unwrap_or_else(|| proc.name.clone());
+        let pid = match proc.combined {
+            Some(count) => format!("{count}×"),
+            None => proc.pid.to_string(),
+        };
+        Row::new(vec![
+            Cell::from(pid).fg(colors.divider),
+            Cell::from(proc.user.clone()).bold().fg(colors.active_user),
+            Cell::from(format!("{:.0}%", proc.usage)).fg(colors.active_cores),
+            Cell::from(proc.peak_usage.map(|peak| format!("{peak:.0}%")).unwrap_or_default())
+                .fg(colors.active_cores)
+                .dim(),
+            Cell::from(format_bytes(proc.rss)),
+            Cell::from(format_bytes(proc.virtual_memory)).dim(),
+            Cell::from(proc.threads.map(|threads| threads.to_string()).unwrap_or_default()),
+            Cell::from(proc.runtime().map(format_duration).unwrap_or_default()),
+            Cell::from(format_rate(proc.io.map(|io| io.read))),
+            Cell::from(format_rate(proc.io.map(|io| io.written))),
+            Cell::from(command).italic().fg(colors.active_task),
+        ])
+    });
+    let gpu_rows = machine.gpus.iter().map(|gpu| {
+        let utilization =
+            gpu.utilization.map(|utilization| format!("{utilization:.0}%")).unwrap_or_default();
+        let users = gpu
+            .processes
+            .iter()
+            .map(|process| format!("{}:{}", process.user, process.name))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Row::new(vec![
+            Cell::from(format!("gpu{}", gpu.index)).fg(colors.divider),
+            Cell::from(gpu.name.clone()),
+            Cell::from(utilization).fg(colors.active_cores),
+            Cell::default(),
+            Cell::from(format_bytes(gpu.mem.used)),
+            Cell::from(format_bytes(gpu.mem.total)).dim(),
+            Cell::default(),
+            Cell::default(),
+            Cell::default(),
+            Cell::default(),
+            Cell::from(users).italic().fg(colors.active_task),
+        ])
+    });
+    let io_row = |kind: &str, name: &str, rates: (u64, u64), note: &str| {
+        Row::new(vec![
+            Cell::from(kind.to_string()).fg(colors.divider),
+            Cell::from(name.to_string()),
+            Cell::default(),
+            Cell::default(),
+            Cell::default(),
+            Cell::default(),
+            Cell::default(),
+            Cell::default(),
+            Cell::from(format_bytes(rates.0)),
+            Cell::from(format_bytes(rates.1)),
+            Cell::from(note.to_string()).fg(colors.divider),
+        ])
+    };
+    let disk_rows = machine
+        .disks
+        .iter()
+        .map(|disk| io_row("disk", &disk.device, (disk.read, disk.written), "read · written"));
+    let network_rows = machine.networks.iter().map(|network| {
+        let rates = (network.received, network.transmitted);
+        io_row("net", &network.interface, rates, "received · transmitted")
+    });
+    // The time of a session is how long its user has been logged in.
+    let session_rows = machine.sessions.iter().map(|session| {
+        let place = match (session.kind, &session.host) {
+            _ if session.is_at_console() => " at the console".to_string(),
+            (SessionKind::Remote, Some(host)) => format!(" from {host}"),
+            _ => String::new(),
+        };
+        let idle = describe_idle(session);
+        Row::new(vec![
+            Cell::from(session.kind.to_string()).fg(colors.divider),
+            Cell::from(session.user.clone()).bold().fg(colors.active_user),
+            Cell::default(),
+            Cell::default(),
+            Cell::default(),
+            Cell::default(),
+            Cell::default(),
+            Cell::from(session.duration().map(format_duration).unwrap_or_default()),
+            Cell::default(),
+            Cell::default(),
+            Cell::from(Line::from(vec![
+                Span::raw(session.line.clone()),
+                Span::raw(format!("{place}{}", idle.unwrap_or_default())).fg(colors.divider),
+            ])),
+        ])
+    });
+
+    let title = Line::from(vec![
+        Span::raw(machine.hostname.clone()).bold().fg(colors.hostname),
+        Span::raw(format!(" ({}) ", machine.room)).fg(colors.room),
+    ]);
+    Table::new(
+        std::iter::once(legend)
+            .chain(job_rows)
+            .chain(process_rows)
+            .chain(gpu_rows)
+            .chain(disk_rows)
+            .chain(network_rows)
+            .chain(session_rows),
+        [
+            Constraint::Length(8),  // PID.
+            Constraint::Length(10), // User.
+            Constraint::Length(5),  // CPU.
+            Constraint::Length(5),  // Peak CPU.
+            Constraint::Length(6),  // RSS.
+            Constraint::Length(6),  // Virtual memory.
+            Constraint::Length(4),  // Threads.
+            Constraint::Length(7),  // Runtime.
+            Constraint::Length(5),  // Read or received.
+            Constraint::Length(5),  // Written or transmitted.
+            Constraint::Fill(1),    // Command.
+        ],
+    )
+    .block(
+        Block::bordered()
+            .title(title)
+            .title(Line::from(describe_memory(machine)).right_aligned())
+            .title_bottom(format!(
+                "{} · Esc to go back",
+                describe(&machine.thresholds, machine.sampling.as_ref())
+            ))
+            .fg(colors.divider),
+    )
+}
+
+/// Lists the hardware of each machine, as far as the hive has gathered it.
+fn inventory<'a>(machines: &[MachineView], colors: &Colors) -> Table<'a> {
+    let legend =
+        Row::new(["Host", "CPU", "Skt", "Cores", "Thr", "Max", "Mem", "Kernel", "Up", "Id"])
+            .fg(colors.legend);
+    let rows = machines.iter().map(|machine| {
+        let hostname = Cell::from(machine.hostname.clone()).fg(colors.hostname);
+        let Some(inventory) = &machine.inventory else {
+            return Row::new(vec![hostname, Cell::from("unknown").fg(colors.divider)]);
+        };
+        let count = |n: Option<u32>| n.map(|n| n.to_string()).unwrap_or_default();
+        // Trademark signs take up space without telling the CPUs apart.
+        let model = ["(R)", "(TM)", "(tm)"]
+            .into_iter()
+            .fold(inventory.cpu_model.clone(), |model, mark| model.replace(mark, ""));
+        let frequency = inventory.max_frequency.map(|mhz| match mhz {
+            ..1000 => format!("{mhz}MHz"),
+            _ => format!("{:.1}GHz", mhz as f64 / 1000.0),
+        });
+        Row::new(vec![
+            hostname,
+            Cell::from(model),
+            Cell::from(count(inventory.sockets)),
+            Cell::from(count(inventory.physical_cores)).fg(colors.cores_total),
+            Cell::from(inventory.logical_cores.to_string()).dim(),
+            Cell::from(frequency.unwrap_or_default()),
+            Cell::from(format_bytes(inventory.memory)),
+            Cell::from(inventory.kernel_version.clone().unwrap_or_default()).dim(),
+            Cell::from(format_duration(inventory.uptime())),
+            // The start of the machine id is enough to tell machines apart.
+            Cell::from(inventory.machine_id.clone().unwrap_or_default()).fg(colors.divider),
+        ])
+    });
+    Table::new(
+        std::iter::once(legend).chain(rows),
+        [
+            Constraint::Length(8), // Hostname.
+            Constraint::Fill(1),   // CPU model.
+            Constraint::Length(3), // Sockets.
+            Constraint::Length(5), // Physical cores.
+            Constraint::Length(4), // Logical cores.
+            Constraint::Length(6), // Maximum frequency.
+            Constraint::Length(6), // Memory.
+            Constraint::Max(16),   // Kernel version.
+            Constraint::Length(7), // Uptime.
+            Constraint::Length(8), // Machine id.
+        ],
+    )
+    .row_highlight_style(Modifier::REVERSED)
+    .block(
+        Block::bordered()
+            .title(Line::from(" Inventory ").bold())
+            .title_bottom("Cores are physical, Thr are logical · i or Esc to go back")
+            .fg(colors.divider),
+    )
+}
+
+/// Describes which processes a bee reported and how, such as `≥10% CPU or ≥4.0G, top 20, mean of
+/// 5 samples over 5.0s`.
+fn describe(thresholds: &Thresholds, sampling: Option<&Sampling>) -> String {
+    let Thresholds { cpu, mem, io, max_processes } = thresholds;
+    let mut description = format!("≥{cpu:.0}% CPU");
+    if let Some(mem) = mem {
+        description += &format!(" or ≥{}", format_bytes(*mem));
+    }
+    if let Some(io) = io {
+        description += &format!(" or ≥{}/s I/O", format_bytes(*io));
+    }
+    if let Some(max_processes) = max_processes {
+        description += &format!(", top {max_processes}");
+    }
+    if let Some(Sampling { window, samples }) = sampling
+        && *samples > 1
+    {
+        description += &format!(", mean of {samples} samples over {window:.1}s");
+    }
+    description
+}
+
+/// Describes the memory, swap, and pressure of a machine, such as
+/// ` 12G of 64G free · swap 1.0G of 8.0G · pressure cpu 1.4% mem 0.0% io 0.2% `. Machines that
+/// did not report any of these are left blank.
+fn describe_memory(machine: &MachineView) -> String {
+    let mut parts = Vec::new();
+    let mem = &machine.mem_usage;
+    if mem.total > 0 {
+        let free = mem.available.unwrap_or(mem.total.saturating_sub(mem.used));
+        parts.push(format!("{} of {} free", format_bytes(free), format_bytes(mem.total)));
     }
+    if let Some(swap) = &machine.swap
+        && swap.total > 0
+    {
+        parts.push(format!("swap {} of {}", format_bytes(swap.used), format_bytes(swap.total)));
+    }
+    let Pressure { cpu, memory, io } = &machine.pressure;
+    let stalls = [("cpu", cpu), ("mem", memory), ("io", io)]
+        .into_iter()
+        .filter_map(|(name, stall)| Some(format!("{name} {:.1}%", stall.as_ref()?.some.avg10)))
+        .collect::<Vec<_>>();
+    if !stalls.is_empty() {
+        parts.push(format!("pressure {}", stalls.join(" ")));
+    }
+    if parts.is_empty() { String::new() } else { format!(" {} ", parts.join(" · ")) }
+}
+
+/// A small horizontal bar that is filled up to `fraction`.
+fn bar<'a>(fraction: f64, color: Color) -> Line<'a> {
+    let length = 5;
+    let nfilled = ((fraction * length as f64) as usize).min(length);
+    let filled = symbols::line::THICK_HORIZONTAL.repeat(nfilled);
+    let empty = symbols::line::HORIZONTAL.repeat(length - nfilled);
+    Line::from(vec![Span::raw(filled).fg(color), Span::raw(empty).fg(color).dim()])
+}
+
+/// Marks how old the usage information of a stale machine is.
+fn stale_marker<'a>(last_seen: Option<std::time::SystemTime>, colors: &Colors) -> Span<'a> {
+    let age = last_seen.and_then(|last_seen| last_seen.elapsed().ok()).unwrap_or_default();
+    Span::raw(format!(" ({} old)", format_duration(age))).fg(colors.stale)
 }
@@ -6,6 +6,7 @@ use ratatui::style::Color;
 pub struct Config {
     pub colors: Colors,
     pub show_room: bool,
+    pub show_temperature: bool,
     pub data_path: PathBuf,
 }
 
@@ -14,6 +15,7 @@ impl Default for Config {
         Self {
             colors: Default::default(),
             show_room: Default::default(),
+            show_temperature: Default::default(),
             data_path: PathBuf::from("/martini/sshuser/mu/mu.dat"),
         }
     }
@@ -43,6 +45,9 @@ pub struct Colors {
     pub active_user: Color,
     pub active_task: Color,
     pub active_cores: Color,
+    pub unavailable: Color,
+    pub stale: Color,
+    pub warning: Color,
     // Gutter.
     pub stats: Color,
     pub notes: Color,
@@ -95,6 +100,9 @@ impl Default for Colors {
             active_user: Color::Gray,
             active_task: Color::Gray,
             active_cores: Color::Gray,
+            unavailable: Color::DarkGray,
+            stale: Color::from_str("#a89984").unwrap(),
+            warning: Color::from_str("#c41829").unwrap(),
             stats: Color::Yellow,
             notes: Color::from_str("#70abaf").unwrap(),
         }
@@ -211,6 +219,10 @@ mod parse {
                 config.show_room =
                     value.parse().context(describe_error(ln, value, keyword, "bool"))?
             }
+            "show_temperature" => {
+                config.show_temperature =
+                    value.parse().context(describe_error(ln, value, keyword, "bool"))?
+            }
             "data_path" => config.data_path = value.into(),
             keyword => bail!("unknown keyword {keyword:?} on line {ln}"),
         }
@@ -247,6 +259,9 @@ mod parse {
             "active_user" => colors.active_user = color?,
             "active_task" => colors.active_task = color?,
             "active_cores" => colors.active_cores = color?,
+            "unavailable" => colors.unavailable = color?,
+            "stale" => colors.stale = color?,
+            "warning" => colors.warning = color?,
             "stats" => colors.stats = color?,
             "notes" => colors.notes = color?,
 
@@ -1,10 +1,40 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use mu::model::{
-    ActiveUser, ClusterData, ClusterUsage, CpuUsage, HostInfo, LoadAvg, MachineDefinition,
-    MachineUsage, Memory, Owner, PROCESS_USAGE_THRESHOLD_PERCENT, Usage,
+    ActiveUser, Capacity, ClusterData, ClusterUsage, CpuUsage, DiskIo, Gpu, GpuProcess, HostInfo,
+    Inventory, Job, LoadAvg, MachineDefinition, MachineUsage, Memory, MountHealth, NetworkIo,
+    Owner, Pressure, Processes, RunInfo, Sampling, Session, Status, Thermal, Thresholds, Usage,
 };
 
+/// Fraction of a file system that may be used before we warn about it.
+pub const DISK_WARNING_FRACTION: f64 = 0.9;
+
+/// Percentage of the last ten seconds during which all tasks were stalled on memory, at which a
+/// machine is considered to be thrashing.
+pub const THRASHING_PRESSURE_PERCENT: f32 = 10.0;
+/// Percentage of the last ten seconds during which some task was stalled on memory, from which the
+/// memory bar is tinted by the pressure.
+pub const MEMORY_PRESSURE_NOTICE_PERCENT: f32 = 1.0;
+/// Fractions of the memory and the swap in use at which a machine is considered to be thrashing,
+/// if it does not report the memory pressure.
+pub const THRASHING_MEMORY_FRACTION: f64 = 0.95;
+pub const THRASHING_SWAP_FRACTION: f64 = 0.5;
+
+/// Temperature in degrees Celsius at which a CPU is considered to run hot, or the number of degrees
+/// below its critical temperature, whichever comes first.
+pub const HOT_TEMPERATURE_CELSIUS: f32 = 85.0;
+pub const HOT_MARGIN_CELSIUS: f32 = 10.0;
+/// Fraction of the base frequency below which busy cores are considered to be throttled.
+///
+/// Cores under load run at least at their base frequency, unless they are throttled.
+pub const THROTTLED_BASE_FRACTION: f64 = 0.9;
+/// Fraction of the maximum frequency below which busy cores are considered to be throttled, if
+/// the base frequency is unknown.
+///
+/// Cores under load normally run close to their maximum, or well below it when many cores share
+/// the power budget. A throttled core runs at a small fraction of it.
+pub const THROTTLED_FREQUENCY_FRACTION: f64 = 0.6;
+
 pub struct ClusterDataView {
     pub header: HeaderView,
     pub stats: StatsView,
@@ -20,14 +50,15 @@ impl ClusterDataView {
         logged: bool,
         success: bool,
         show_room: bool,
+        show_temperature: bool,
     ) -> Self {
         let header = HeaderView::new(hostinfo, &data.usage);
         let stats = StatsView::new(&data.usage);
-        let notes = NotesView::new(&data, logged, success);
+        let notes = NotesView::new(data, logged, success);
         let mut machines = data
             .usage
             .iter()
-            .map(|machine| MachineView::new(machine, show_room))
+            .map(|machine| MachineView::new(machine, show_room, show_temperature))
             .collect::<Box<[_]>>();
         machines.sort_by_key(|machine| machine.hostname.clone());
         Self { header, stats, notes, machines }
@@ -41,11 +72,13 @@ pub struct HeaderView {
 
 impl HeaderView {
     pub fn new(hostinfo: HostInfo, usage: &ClusterUsage) -> Self {
+        // Machines that are down would inflate the totals with their last known usage.
         let total_cores_used: f32 =
-            usage.iter().map(|entry| entry.usage.cpus.iter().sum::<f32>()).sum();
-        let total_cores: f32 =
-            usage.iter().map(|entry| entry.usage.cpus.len() as f32 * 100.0).sum();
-        Self { hostinfo, total_usage: total_cores_used / total_cores }
+            usage.current().map(|usage| usage.cpus.iter().sum::<f32>()).sum();
+        let total_cores: f32 = usage.current().map(|usage| usage.cpus.len() as f32 * 100.0).sum();
+        // If no machine reported any usage, we don't want to divide by zero.
+        let total_usage = if total_cores > 0.0 { total_cores_used / total_cores } else { 0.0 };
+        Self { hostinfo, total_usage }
     }
 }
 
@@ -61,12 +94,14 @@ impl std::ops::Deref for StatsView {
 }
 
 impl StatsView {
-    pub fn new<'a>(usage: &'a ClusterUsage) -> Self {
+    pub fn new(usage: &ClusterUsage) -> Self {
         // Create a list of `(user, total_threads)` pairs.
         let mut tpu = HashMap::<_, usize>::new();
-        for machine in usage.iter() {
-            for (user, procs) in machine.usage.processes.by_users() {
-                *tpu.entry(user).or_default() += procs.len();
+        for usage in usage.current() {
+            let Usage { processes, jobs, thresholds, .. } = usage;
+            for (user, tasks) in tasks_by_user(processes, jobs, thresholds) {
+                let cores = tasks.iter().map(|task| task.cores as usize).sum::<usize>();
+                *tpu.entry(user).or_default() += cores;
             }
         }
 
@@ -96,13 +131,31 @@ impl StatsView {
 
 pub struct NotesView {
     pub last_update: std::time::SystemTime,
+    pub run: Option<RunInfo>,
+    /// Shared mounts that are nearly full, as `(path, fraction_used)` pairs.
+    pub full_mounts: Box<[(String, f64)]>,
     pub logged: bool,
     pub success: bool,
 }
 
 impl NotesView {
     fn new(data: &ClusterData, logged: bool, success: bool) -> Self {
-        Self { last_update: data.time(), logged, success }
+        // A shared mount is seen by many machines. The fullest report of it is the one we trust.
+        let mut shared = HashMap::<&str, f64>::new();
+        for usage in data.usage.iter().filter_map(|machine| machine.usage.as_ref()) {
+            for mount in usage.mounts.iter().filter(|mount| mount.remote) {
+                let Some(space) = mount.known_space() else { continue };
+                let fraction = shared.entry(&mount.path).or_default();
+                *fraction = fraction.max(space.fraction_used());
+            }
+        }
+        let mut full_mounts = shared
+            .into_iter()
+            .filter(|&(_, fraction)| fraction >= DISK_WARNING_FRACTION)
+            .map(|(path, fraction)| (path.to_string(), fraction))
+            .collect::<Box<[_]>>();
+        full_mounts.sort_by(|a, b| a.0.cmp(&b.0));
+        Self { last_update: data.time(), run: data.run.clone(), full_mounts, logged, success }
     }
 }
 
@@ -110,37 +163,405 @@ pub struct MachineView {
     pub hostname: String,
     pub owner: Owner,
     pub room: String,
+    /// Cores in use, counting the hyperthreads of a physical core as one core if the topology is
+    /// known.
     pub cpu_usage: CpuUsage,
     pub mem_usage: Memory,
+    /// Swap space, if the bee reported it.
+    pub swap: Option<Memory>,
+    pub pressure: Pressure,
+    pub processes: Processes,
+    pub jobs: Box<[Job]>,
+    pub gpus: Box<[Gpu]>,
+    pub networks: Box<[NetworkIo]>,
+    pub disks: Box<[DiskIo]>,
+    /// Space of the fullest local file system, if the machine reported any.
+    pub disk_usage: Option<Capacity>,
+    /// Mounts that did not respond promptly, as `(path, health)` pairs, worst first.
+    pub troubled_mounts: Box<[(String, MountHealth)]>,
     pub load_avg: LoadAvg,
+    /// The thresholds by which the bee selected the processes.
+    pub thresholds: Thresholds,
+    /// How the bee sampled the CPU usage, if it reported it.
+    pub sampling: Option<Sampling>,
     pub active_user: Option<ActiveUser>,
     pub show_room: bool,
+    /// Temperature of the hottest CPU sensor in degrees Celsius, if the bee reported any.
+    pub temperature: Option<f32>,
+    /// Whether a CPU is running close to the temperature at which it is throttled.
+    pub hot: bool,
+    /// Whether the busy cores run well below their maximum frequency.
+    pub throttled: bool,
+    pub show_temperature: bool,
+    /// The users that are logged in.
+    pub sessions: Box<[Session]>,
+    /// Labels of the machine, as set in the machines file of the hive.
+    pub tags: Box<[String]>,
+    pub status: Status,
+    /// Whether the usage is the last known usage of a machine that could not be reached.
+    pub stale: bool,
+    pub last_seen: Option<std::time::SystemTime>,
+    /// The hardware inventory, if the hive has gathered it.
+    pub inventory: Option<Inventory>,
 }
 
 impl MachineView {
-    pub fn new(machine: &MachineUsage, show_room: bool) -> Self {
+    pub fn new(machine: &MachineUsage, show_room: bool, show_temperature: bool) -> Self {
         // TODO: Consider doing the whole lifetime thing here.
-        let MachineDefinition { hostname, owner, room } = machine.definition.clone();
-        let Usage { global_cpu_usage: _, cpus, load_avg, mem: _, processes } =
-            machine.usage.clone();
-        let cpu_usage = CpuUsage {
-            used: cpus.iter().filter(|&&u| u > PROCESS_USAGE_THRESHOLD_PERCENT).count() as u32,
+        let MachineDefinition { hostname, owner, room, tags } = machine.definition.clone();
+        let status = machine.status;
+        let stale = machine.is_stale();
+        let last_seen = machine
+            .last_seen
+            .map(|ts| std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(ts));
+        // A machine without usage information is shown with empty figures.
+        let Usage {
+            global_cpu_usage: _,
+            cpus,
+            cpus_peak: _,
+            sampling,
+            load_avg,
+            mem: mem_usage,
+            swap,
+            pressure,
+            processes,
+            mounts,
+            gpus,
+            thresholds,
+            jobs,
+            networks,
+            disks,
+            thermal,
+            sessions,
+            boot_time: _,
+            inventory: _,
+        } = machine.usage.clone().unwrap_or_default();
+        // Shared mounts are reported for the whole cluster rather than for each machine.
+        let disk_usage = mounts
+            .iter()
+            .filter(|mount| !mount.remote)
+            .filter_map(|mount| mount.known_space())
+            .max_by(|a, b| a.fraction_used().total_cmp(&b.fraction_used()));
+        let mut troubled_mounts = mounts
+            .iter()
+            .filter(|mount| !mount.health.is_healthy())
+            .map(|mount| (mount.path.clone(), mount.health))
+            .collect::<Box<[_]>>();
+        troubled_mounts.sort_by_key(|&(_, health)| health != MountHealth::Hung);
+        let inventory = machine.inventory.clone();
+        let cpu_usage = cpu_usage(&cpus, thresholds.cpu, inventory.as_ref());
+        let hottest = thermal.hottest();
+        let hot = hottest.is_some_and(|sensor| {
+            let critical = sensor.critical.map(|critical| critical - HOT_MARGIN_CELSIUS);
+            let hot = critical.map_or(HOT_TEMPERATURE_CELSIUS, |c| c.min(HOT_TEMPERATURE_CELSIUS));
+            sensor.temperature >= hot
+        });
+        let temperature = hottest.map(|sensor| sensor.temperature);
+        let throttled = is_throttled(&cpus, &thermal, thresholds.cpu);
+        let active_user = active_user(tasks_by_user(&processes, &jobs, &thresholds), &gpus);
+        Self {
+            hostname,
+            owner,
+            room,
+            mem_usage,
+            swap,
+            pressure,
+            processes,
+            jobs,
+            gpus,
+            networks,
+            disks,
+            disk_usage,
+            troubled_mounts,
+            cpu_usage,
+            load_avg,
+            thresholds,
+            sampling,
+            active_user,
+            show_room,
+            temperature,
+            hot,
+            throttled,
+            show_temperature,
+            sessions,
+            tags,
+            status,
+            stale,
+            last_seen,
+            inventory,
+        }
+    }
+
+    /// Returns whether the machine spends much of its time waiting for memory to be swapped in.
+    ///
+    /// The memory pressure tells us directly, if the kernel reports it. Otherwise, a machine that
+    /// has nearly run out of memory while using much of its swap is likely thrashing.
+    pub fn is_thrashing(&self) -> bool {
+        if let Some(memory) = &self.pressure.memory {
+            let stalled = memory.full.unwrap_or(memory.some).avg10;
+            return stalled >= THRASHING_PRESSURE_PERCENT;
+        }
+        let swapping = self.swap.as_ref().is_some_and(|swap| {
+            swap.total > 0 && swap.used as f64 / swap.total as f64 >= THRASHING_SWAP_FRACTION
+        });
+        swapping && self.mem_usage.fraction_used() >= THRASHING_MEMORY_FRACTION
+    }
+
+    /// Returns the percentage of the last ten seconds during which some task was stalled on
+    /// memory, if the bee reported it and it is worth noticing.
+    pub fn memory_pressure(&self) -> Option<f32> {
+        let memory = self.pressure.memory.as_ref()?;
+        Some(memory.some.avg10).filter(|&avg10| avg10 >= MEMORY_PRESSURE_NOTICE_PERCENT)
+    }
+
+    /// Returns the session of the user sitting at the machine, if anyone is.
+    ///
+    /// Someone logged in at the display may have several sessions there, such as the display
+    /// itself and the terminals they opened on it. The one with the most recent input tells how
+    /// long they have been away.
+    pub fn at_console(&self) -> Option<&Session> {
+        self.sessions
+            .iter()
+            .filter(|session| session.is_at_console())
+            .min_by_key(|session| session.idle.unwrap_or(u64::MAX))
+    }
+}
+
+/// Counts the cores in use, given the usage of each logical CPU in percent.
+///
+/// The hyperthreads of a physical core share its execution units, so a physical core is in use as
+/// soon as any of its hyperthreads is. Without the topology from the inventory, each logical CPU
+/// counts as a core.
+fn cpu_usage(cpus: &[f32], threshold: f32, inventory: Option<&Inventory>) -> CpuUsage {
+    let core_of_cpu = inventory
+        .map(|inventory| &inventory.core_of_cpu)
+        .filter(|core_of_cpu| !core_of_cpu.is_empty() && core_of_cpu.len() == cpus.len());
+    let Some(core_of_cpu) = core_of_cpu else {
+        return CpuUsage {
+            used: cpus.iter().filter(|&&u| u > threshold).count() as u32,
             total: cpus.len() as u32,
         };
-        let mem_usage = machine.usage.mem.clone();
-        let active_user = processes
-            .by_users()
-            .into_iter()
-            .max_by_key(|(_, cores)| cores.iter().map(|cu| cu.usage as u64).sum::<u64>())
-            .map(|(user, procs)| ActiveUser {
-                user: user.to_string(),
-                cores: procs.len() as u32,
-                task: procs
+    };
+    let used = core_of_cpu
+        .iter()
+        .zip(cpus)
+        .filter(|&(_, &u)| u > threshold)
+        .map(|(&core, _)| core)
+        .collect::<BTreeSet<_>>();
+    let total = core_of_cpu.iter().collect::<BTreeSet<_>>();
+    CpuUsage { used: used.len() as u32, total: total.len() as u32 }
+}
+
+/// Returns whether the CPUs are being throttled.
+///
+/// Where the kernel counts the throttling, that is all we need. Otherwise, we look for busy cores
+/// that run below their base frequency, or well below the maximum frequency if the base frequency
+/// is unknown. Idle cores are left out, since they lower their frequency to save power.
+fn is_throttled(cpus: &[f32], thermal: &Thermal, threshold: f32) -> bool {
+    if let Some(throttled_cpus) = thermal.throttled_cpus {
+        return throttled_cpus > 0;
+    }
+    let expected = match (thermal.base_frequency, thermal.max_frequency) {
+        (Some(base), _) if base > 0 => THROTTLED_BASE_FRACTION * base as f64,
+        (_, Some(max)) if max > 0 => THROTTLED_FREQUENCY_FRACTION * max as f64,
+        _ => return false,
+    };
+    let busy = cpus
+        .iter()
+        .zip(&thermal.frequencies)
+        .filter(|&(&usage, _)| usage > threshold)
+        .map(|(_, &frequency)| frequency as f64)
+        .collect::<Vec<_>>();
+    if busy.is_empty() {
+        return false;
+    }
+    let mean = busy.iter().sum::<f64>() / busy.len() as f64;
+    mean < expected
+}
+
+/// Something a user is running on a machine.
+struct Task<'u> {
+    name: &'u str,
+    /// CPU usage in percent of a single core.
+    usage: f32,
+    cores: u32,
+    runtime: Option<std::time::Duration>,
+}
+
+/// Returns the tasks of each user on a machine that take up at least a core.
+///
+/// The tasks are the jobs if the bee reports them, since a job started by a single command can
+/// consist of many processes. Bees that predate jobs report none, and then each process is a task.
+fn tasks_by_user<'u>(
+    processes: &'u Processes,
+    jobs: &'u [Job],
+    thresholds: &Thresholds,
+) -> HashMap<&'u str, Vec<Task<'u>>> {
+    let mut tasks = HashMap::<&str, Vec<Task>>::new();
+    if jobs.is_empty() {
+        // Processes that are only reported for their memory do not take up a core.
+        for proc in processes.iter().filter(|proc| thresholds.uses_core(proc)) {
+            let task = Task {
+                name: &proc.name,
+                usage: proc.usage,
+                cores: proc.cores(),
+                runtime: proc.runtime(),
+            };
+            tasks.entry(&proc.user).or_default().push(task);
+        }
+    } else {
+        for job in jobs.iter().filter(|job| job.usage >= thresholds.cpu) {
+            let task = Task {
+                name: &job.name,
+                usage: job.usage,
+                cores: job.cores(),
+                runtime: job.runtime(),
+            };
+            tasks.entry(&job.user).or_default().push(task);
+        }
+    }
+    tasks
+}
+
+/// Picks the user that is making the most use of a machine, given the tasks of each user.
+///
+/// Keeping a GPU busy outweighs any amount of CPU use, since the GPUs are the scarcer resource.
+/// Among users keeping the same number of GPUs busy, the one using the most CPU wins.
+fn active_user(cpu_users: HashMap<&str, Vec<Task>>, gpus: &[Gpu]) -> Option<ActiveUser> {
+    // For each user, the number of busy GPUs they run processes on, and their largest GPU process.
+    let mut gpu_users = HashMap::<&str, (u32, &GpuProcess)>::new();
+    for gpu in gpus.iter().filter(|gpu| gpu.is_busy()) {
+        let mut seen = Vec::new();
+        for process in &gpu.processes {
+            let (busy, largest) = gpu_users.entry(&process.user).or_insert((0, process));
+            if !seen.contains(&process.user.as_str()) {
+                seen.push(&process.user);
+                *busy += 1;
+            }
+            if process.mem > largest.mem {
+                *largest = process;
+            }
+        }
+    }
+
+    let users = cpu_users.keys().chain(gpu_users.keys()).copied().collect::<BTreeSet<_>>();
+    users
+        .into_iter()
+        .map(|user| {
+            let tasks = cpu_users.get(user).map(|tasks| &tasks[..]).unwrap_or_default();
+            let cpu = tasks.iter().map(|task| task.usage as u64).sum::<u64>();
+            let gpu = gpu_users.get(user);
+            let busy_gpus = gpu.map(|&(busy, _)| busy).unwrap_or_default();
+            ((busy_gpus, cpu), user, tasks, gpu)
+        })
+        .max_by_key(|&(score, user, ..)| (score, std::cmp::Reverse(user)))
+        .map(|((gpus, _), user, tasks, gpu)| {
+            let task = match gpu {
+                Some((_, largest)) => largest.name.to_string(),
+                None => tasks
                     .iter()
-                    .max_by_key(|proc| proc.usage as u64)
-                    .map(|cu| cu.name.to_string())
+                    .max_by_key(|task| task.usage as u64)
+                    .map(|task| task.name.to_string())
                     .unwrap_or("?".to_string()),
-            });
-        Self { hostname, owner, room, mem_usage, cpu_usage, load_avg, active_user, show_room }
+            };
+            let runtime =
+                tasks.iter().filter(|t| t.name == task).filter_map(|task| task.runtime).max();
+            let cores = tasks.iter().map(|task| task.cores).sum();
+            ActiveUser { user: user.to_string(), cores, gpus, task, runtime }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Four busy cores running at `mhz`.
+    fn thermal(mhz: u64, max: Option<u64>, base: Option<u64>, throttled: Option<u32>) -> Thermal {
+        Thermal {
+            sensors: Box::default(),
+            frequencies: vec![mhz; 4].into(),
+            max_frequency: max,
+            base_frequency: base,
+            throttled_cpus: throttled,
+        }
+    }
+
+    const BUSY: &[f32] = &[100.0; 4];
+
+    #[test]
+    fn base_clock_is_not_throttled() {
+        // A Xeon with all cores busy runs at its base clock, far below its turbo clock.
+        assert!(!is_throttled(BUSY, &thermal(2100, Some(3700), Some(2100), None), 10.0));
+        assert!(is_throttled(BUSY, &thermal(1200, Some(3700), Some(2100), None), 10.0));
+    }
+
+    #[test]
+    fn throttle_counts_decide() {
+        assert!(!is_throttled(BUSY, &thermal(1200, Some(3700), Some(2100), Some(0)), 10.0));
+        assert!(is_throttled(BUSY, &thermal(3500, Some(3700), Some(2100), Some(2)), 10.0));
+    }
+
+    #[test]
+    fn falls_back_to_the_maximum_frequency() {
+        assert!(!is_throttled(BUSY, &thermal(3000, Some(3700), None, None), 10.0));
+        assert!(is_throttled(BUSY, &thermal(1500, Some(3700), None, None), 10.0));
+        assert!(!is_throttled(BUSY, &thermal(1500, None, None, None), 10.0));
+        // Idle cores lower their frequency to save power.
+        assert!(!is_throttled(&[0.0; 4], &thermal(800, Some(3700), None, None), 10.0));
+    }
+
+    #[test]
+    fn falls_back_past_an_unknown_base_frequency() {
+        assert!(!is_throttled(BUSY, &thermal(3000, Some(3700), Some(0), None), 10.0));
+        assert!(is_throttled(BUSY, &thermal(1500, Some(3700), Some(0), None), 10.0));
+        assert!(!is_throttled(BUSY, &thermal(1500, Some(0), None, None), 10.0));
+    }
+
+    #[test]
+    fn idle_machines_are_not_throttled() {
+        let slow = thermal(800, Some(3700), Some(2100), None);
+        assert!(!is_throttled(&[0.0; 4], &slow, 10.0));
+        // Cores at the threshold are not busy.
+        assert!(!is_throttled(&[10.0; 4], &slow, 10.0));
+        assert!(!is_throttled(&[], &thermal(800, Some(3700), None, None), 10.0));
+        // A single busy core among idle ones is judged by itself.
+        let mut mixed = slow;
+        mixed.frequencies = vec![800, 800, 800, 3500].into();
+        assert!(!is_throttled(&[0.0, 0.0, 0.0, 100.0], &mixed, 10.0));
+        assert!(is_throttled(&[100.0, 0.0, 0.0, 0.0], &mixed, 10.0));
+    }
+
+    fn gpu(utilization: f32, user: &str) -> Gpu {
+        let process = GpuProcess {
+            pid: 1,
+            name: "train".to_string(),
+            user: user.to_string(),
+            mem: Some(1 << 30),
+        };
+        Gpu {
+            index: 0,
+            name: "A100".to_string(),
+            utilization: Some(utilization),
+            mem: Memory { total: 40 << 30, used: 1 << 30, available: None },
+            processes: Box::new([process]),
+        }
+    }
+
+    #[test]
+    fn busy_gpus_outweigh_cpu_use() {
+        let task = || Task { name: "make", usage: 800.0, cores: 8, runtime: None };
+        let cpu_users = || HashMap::from([("bob", vec![task()])]);
+        // A GPU is busy by its own threshold, whatever the CPU threshold of the machine is.
+        let busy = gpu(mu::model::GPU_BUSY_THRESHOLD_PERCENT + 5.0, "marieke");
+        let active = active_user(cpu_users(), &[busy]).unwrap();
+        assert_eq!(
+            (active.user.as_str(), active.gpus, active.task.as_str()),
+            ("marieke", 1, "train")
+        );
+
+        let idle = gpu(mu::model::GPU_BUSY_THRESHOLD_PERCENT - 5.0, "marieke");
+        assert!(!idle.is_busy());
+        let active = active_user(cpu_users(), &[idle]).unwrap();
+        assert_eq!((active.user.as_str(), active.cores, active.task.as_str()), ("bob", 8, "make"));
     }
 }
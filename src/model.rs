@@ -1,5 +1,13 @@
+/// CPU usage in percent of a single core at which a process is reported, unless the bee is
+/// configured otherwise.
 pub const PROCESS_USAGE_THRESHOLD_PERCENT: f32 = 10.0;
 
+/// Utilization in percent above which a GPU counts as busy.
+///
+/// This is independent of the CPU threshold, which is configured per machine and measures
+/// something else entirely.
+pub const GPU_BUSY_THRESHOLD_PERCENT: f32 = 10.0;
+
 /// Identity of a cluster of machines.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct ClusterDefinition(Box<[MachineDefinition]>);
@@ -10,6 +18,9 @@ pub struct MachineDefinition {
     pub hostname: String,
     pub owner: Owner,
     pub room: String,
+    /// Free-form labels for this machine, as set in the machines file of the hive.
+    #[serde(default)]
+    pub tags: Box<[String]>,
 }
 
 /// Usage information for a cluster of machines.
@@ -21,10 +32,19 @@ impl ClusterUsage {
         Self(usages)
     }
 
+    /// Returns the usage of the machines that were reached, leaving out the last known usage of
+    /// the machines that were not.
+    pub fn current(&self) -> impl Iterator<Item = &Usage> {
+        self.iter()
+            .filter(|machine| !machine.is_stale())
+            .filter_map(|machine| machine.usage.as_ref())
+    }
+
+    /// Counts the CPUs of the machines that were reached.
     pub fn cpu_count(&self) -> u32 {
         let mut cpu_count = 0;
-        for machine in self.iter() {
-            cpu_count += machine.usage.cpus.len();
+        for usage in self.current() {
+            cpu_count += usage.cpus.len();
         }
         cpu_count as u32
     }
@@ -42,17 +62,352 @@ impl std::ops::Deref for ClusterUsage {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct MachineUsage {
     pub definition: MachineDefinition,
-    pub usage: Usage,
+    /// Outcome of the most recent attempt to gather the usage of this machine.
+    #[serde(default)]
+    pub status: Status,
+    /// Description of what went wrong, if the status is not [`Status::Ok`].
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Unix timestamp of the last time the usage of this machine was gathered successfully.
+    #[serde(default)]
+    pub last_seen: Option<u64>,
+    /// The hardware inventory, which is gathered rarely and carried over between runs.
+    #[serde(default)]
+    pub inventory: Option<Inventory>,
+    /// The usage information, if it could be gathered.
+    ///
+    /// If the status is not [`Status::Ok`], this may be the last known usage carried over from a
+    /// previous run. See [`MachineUsage::is_stale`].
+    pub usage: Option<Usage>,
+    /// The header sent by the bee, if it sent one.
+    #[serde(default)]
+    pub bee: Option<crate::protocol::Header>,
+    /// How long it took to gather the usage of this machine.
+    #[serde(default)]
+    pub timing: Option<Timing>,
+}
+
+/// Timing of the attempts at gathering the usage of a machine.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct Timing {
+    /// Number of attempts that were made.
+    pub attempts: u32,
+    /// Total time spent on this machine in seconds, including any waiting between attempts.
+    pub duration: f32,
+}
+
+impl MachineUsage {
+    /// Returns whether the usage information is carried over from an earlier successful attempt.
+    pub fn is_stale(&self) -> bool {
+        !self.status.is_ok() && self.usage.is_some()
+    }
 }
 
 /// Usage information for a single machine.
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct Usage {
+    /// Mean usage of all cores over the sampling window, in percent.
     pub global_cpu_usage: f32,
+    /// Mean usage of each core over the sampling window, in percent.
     pub cpus: Box<[f32]>,
+    /// Peak usage of each core over the sampling window, in percent.
+    ///
+    /// Bees that do not report it took a single sample, for which the peak equals the mean.
+    #[serde(default)]
+    pub cpus_peak: Box<[f32]>,
+    /// How the CPU usage was sampled, if the bee reported it.
+    #[serde(default)]
+    pub sampling: Option<Sampling>,
     pub load_avg: LoadAvg,
     pub mem: Memory,
+    /// Swap space in bytes, if the bee reported it.
+    #[serde(default)]
+    pub swap: Option<Memory>,
+    /// How much the machine is held up by a lack of CPU, memory, or IO.
+    #[serde(default)]
+    pub pressure: Pressure,
     pub processes: Processes,
+    /// Usage of the mounted file systems that the bee was configured to report.
+    #[serde(default)]
+    pub mounts: Box<[Mount]>,
+    /// Usage of the GPUs, if the bee was configured to query them.
+    #[serde(default)]
+    pub gpus: Box<[Gpu]>,
+    /// The thresholds by which the bee selected the reported processes.
+    ///
+    /// Bees that do not report them used the defaults.
+    #[serde(default)]
+    pub thresholds: Thresholds,
+    /// Groups of processes that were started together, selected by the same thresholds as the
+    /// processes.
+    #[serde(default)]
+    pub jobs: Box<[Job]>,
+    /// Throughput of the network interfaces over the sampling window.
+    #[serde(default)]
+    pub networks: Box<[NetworkIo]>,
+    /// Throughput of the block devices over the sampling window.
+    #[serde(default)]
+    pub disks: Box<[DiskIo]>,
+    /// Temperatures and frequencies of the CPUs.
+    #[serde(default)]
+    pub thermal: Thermal,
+    /// The users that are logged in.
+    #[serde(default)]
+    pub sessions: Box<[Session]>,
+    /// Unix timestamp of when the machine was booted, if the bee reported it.
+    #[serde(default)]
+    pub boot_time: Option<u64>,
+    /// The hardware inventory, if the hive asked for it.
+    #[serde(default)]
+    pub inventory: Option<Inventory>,
+}
+
+/// The hardware and operating system of a machine, which rarely change.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Inventory {
+    /// Unix timestamp of when the inventory was gathered.
+    pub gathered: u64,
+    pub cpu_model: String,
+    /// Number of CPU packages, if known.
+    pub sockets: Option<u32>,
+    /// Number of physical cores, if known. Each may run several logical CPUs with hyperthreading.
+    pub physical_cores: Option<u32>,
+    pub logical_cores: u32,
+    /// The physical core of each logical CPU, in the order of [`Usage::cpus`], if known.
+    #[serde(default)]
+    pub core_of_cpu: Box<[u32]>,
+    /// Highest frequency that any core can run at in MHz, if known.
+    pub max_frequency: Option<u64>,
+    /// Total memory in bytes.
+    pub memory: u64,
+    pub os: Option<String>,
+    pub kernel_version: Option<String>,
+    /// Unix timestamp of when the machine was booted.
+    pub boot_time: u64,
+    /// The identifier in `/etc/machine-id`, which stays the same when the hostname changes.
+    pub machine_id: Option<String>,
+}
+
+impl Inventory {
+    /// Returns how long the machine has been up.
+    pub fn uptime(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(unix_timestamp().saturating_sub(self.boot_time))
+    }
+}
+
+/// A group of processes that were started together, such as the ranks of an `mpirun`.
+///
+/// A job is a process group of a single user. Its root is the process that started the group, or
+/// the earliest remaining member if that process is gone.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Job {
+    /// The process group id, which is the pid of the process that started the job.
+    pub pgid: u32,
+    /// Name of the root process.
+    pub name: String,
+    pub user: String,
+    /// Command line of the root process, sanitized like [`Process::command`].
+    pub command: Option<String>,
+    /// Summed CPU usage of the members, in percent of a single core.
+    pub usage: f32,
+    /// Summed resident set size of the members in bytes.
+    pub rss: u64,
+    /// Number of processes in the job.
+    pub processes: u32,
+    /// Unix timestamp of when the earliest member was started, if known.
+    pub start_time: Option<u64>,
+}
+
+impl Job {
+    /// Returns the number of cores the job takes up, judged by its summed usage.
+    pub fn cores(&self) -> u32 {
+        ((self.usage / 100.0).round() as u32).max(1)
+    }
+
+    /// Returns how long the job has been running, if its start time is known.
+    pub fn runtime(&self) -> Option<std::time::Duration> {
+        runtime_since(self.start_time)
+    }
+}
+
+/// How a bee sampled the CPU usage.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct Sampling {
+    /// Length of the sampling window in seconds.
+    pub window: f32,
+    /// Number of samples taken over the window.
+    pub samples: u32,
+}
+
+/// The policy by which a bee selects the processes it reports.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct Thresholds {
+    /// CPU usage in percent of a single core at or above which a process is reported.
+    ///
+    /// This is also the usage at or above which a core is counted as used.
+    pub cpu: f32,
+    /// Resident memory in bytes at or above which a process is reported regardless of its CPU
+    /// usage, if set.
+    pub mem: Option<u64>,
+    /// Disk throughput in bytes per second at or above which a process is reported regardless of
+    /// its CPU usage, if set.
+    #[serde(default)]
+    pub io: Option<u64>,
+    /// Largest number of processes that is reported, if limited. The processes using the most CPU
+    /// are reported first.
+    pub max_processes: Option<u32>,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self { cpu: PROCESS_USAGE_THRESHOLD_PERCENT, mem: None, io: None, max_processes: None }
+    }
+}
+
+impl Thresholds {
+    /// Returns whether a process takes up a core, as opposed to being reported for its memory or
+    /// its disk throughput.
+    pub fn uses_core(&self, process: &Process) -> bool {
+        process.usage >= self.cpu
+    }
+}
+
+/// Usage of a single GPU.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Gpu {
+    pub index: u32,
+    pub name: String,
+    /// Percentage of time over the past sample period during which the GPU was busy, if known.
+    pub utilization: Option<f32>,
+    /// Memory in bytes.
+    pub mem: Memory,
+    pub processes: Box<[GpuProcess]>,
+}
+
+impl Gpu {
+    /// Whether the GPU is doing a significant amount of work.
+    pub fn is_busy(&self) -> bool {
+        self.utilization.is_some_and(|utilization| utilization > GPU_BUSY_THRESHOLD_PERCENT)
+    }
+}
+
+/// A process running on a GPU.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GpuProcess {
+    pub pid: u32,
+    pub name: String,
+    pub user: String,
+    /// GPU memory used by the process in bytes, if known.
+    pub mem: Option<u64>,
+}
+
+/// Usage of a mounted file system.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Mount {
+    /// Path at which the file system is mounted.
+    pub path: String,
+    pub fs_type: String,
+    /// Whether the file system lives on another machine, such as an NFS share.
+    pub remote: bool,
+    /// How quickly the file system responded when it was probed.
+    #[serde(default)]
+    pub health: MountHealth,
+    /// Space in bytes, which is zero if the file system did not respond. See
+    /// [`Mount::known_space`].
+    ///
+    /// This is not an [`Option`], since older hives and viewers expect it to be present.
+    pub space: Capacity,
+    /// Number of inodes, if the file system has a fixed number of them.
+    pub inodes: Option<Capacity>,
+}
+
+impl Mount {
+    /// Returns the space in bytes, unless the file system did not respond.
+    pub fn known_space(&self) -> Option<Capacity> {
+        (self.health != MountHealth::Hung).then_some(self.space)
+    }
+}
+
+/// How quickly a mounted file system responded when it was probed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum MountHealth {
+    #[default]
+    Healthy,
+    /// The file system responded, but took longer than it should.
+    Slow,
+    /// The file system did not respond in time. Processes touching it are likely stuck.
+    Hung,
+}
+
+impl MountHealth {
+    pub fn is_healthy(&self) -> bool {
+        *self == Self::Healthy
+    }
+}
+
+impl std::fmt::Display for MountHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MountHealth::Healthy => write!(f, "healthy"),
+            MountHealth::Slow => write!(f, "slow"),
+            MountHealth::Hung => write!(f, "hung"),
+        }
+    }
+}
+
+/// Total, used, and available amounts of a file system resource.
+///
+/// Some of the total may be reserved for the superuser, such that `used + available` can be smaller
+/// than `total`.
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub struct Capacity {
+    pub total: u64,
+    pub used: u64,
+    pub available: u64,
+}
+
+impl Capacity {
+    /// Returns the fraction of the capacity that is used, out of what is usable by normal users.
+    ///
+    /// This is the same percentage that `df` reports.
+    pub fn fraction_used(&self) -> f64 {
+        let usable = self.used + self.available;
+        if usable == 0 { 0.0 } else { self.used as f64 / usable as f64 }
+    }
+}
+
+/// Outcome of an attempt to gather the usage of a machine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Status {
+    #[default]
+    Ok,
+    /// The machine could not be reached, typically over ssh.
+    SshFailure,
+    /// The bee exited unsuccessfully with the given exit code, or was killed by a signal.
+    BeeExit(Option<i32>),
+    /// The output of the bee could not be deserialized.
+    Deserialization,
+    /// The machine did not respond in time.
+    Timeout,
+}
+
+impl Status {
+    pub fn is_ok(&self) -> bool {
+        *self == Self::Ok
+    }
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::Ok => write!(f, "ok"),
+            Status::SshFailure => write!(f, "ssh failure"),
+            Status::BeeExit(Some(code)) => write!(f, "bee exit {code}"),
+            Status::BeeExit(None) => write!(f, "bee killed"),
+            Status::Deserialization => write!(f, "bad bee output"),
+            Status::Timeout => write!(f, "timed out"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
@@ -66,7 +421,7 @@ pub enum Owner {
 }
 
 impl std::str::FromStr for Owner {
-    type Err = ();
+    type Err = ParseOwnerError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let s = s.trim();
@@ -76,17 +431,60 @@ impl std::str::FromStr for Owner {
         if s == "Reservation Required" {
             return Ok(Self::Reserve);
         }
+        if s.eq_ignore_ascii_case("Reservation Required") {
+            return Err(ParseOwnerError::Reservation(s.to_string()));
+        }
         if let Some(name) = s.strip_suffix("(Student)") {
-            return Ok(Self::Student(name.trim_end().to_string()));
+            return owner_name(name, "Student").map(Self::Student);
         }
         if let Some(name) = s.strip_suffix("(Visitor)") {
-            return Ok(Self::Visitor(name.trim_end().to_string()));
+            return owner_name(name, "Visitor").map(Self::Visitor);
+        }
+        // Any other parenthesized remark is most likely a misspelled role.
+        if let Some(rest) = s.strip_suffix(')')
+            && let Some((_, role)) = rest.rsplit_once('(')
+        {
+            return Err(ParseOwnerError::UnknownRole(role.to_string()));
         }
 
         Ok(Self::Member(s.to_string()))
     }
 }
 
+/// Returns the name that precedes a `(role)` suffix, which must not be empty.
+fn owner_name(name: &str, role: &'static str) -> Result<String, ParseOwnerError> {
+    match name.trim_end() {
+        "" => Err(ParseOwnerError::MissingName(role)),
+        name => Ok(name.to_string()),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseOwnerError {
+    /// A role without the name of the owner, such as `(Student)`.
+    MissingName(&'static str),
+    /// A parenthesized role that is not `(Student)` or `(Visitor)`.
+    UnknownRole(String),
+    /// A reservation note with unexpected capitalization.
+    Reservation(String),
+}
+
+impl std::fmt::Display for ParseOwnerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseOwnerError::MissingName(role) => write!(f, "expected a name before ({role})"),
+            ParseOwnerError::UnknownRole(role) => {
+                write!(f, "unknown role ({role}), expected (Student) or (Visitor)")
+            }
+            ParseOwnerError::Reservation(note) => {
+                write!(f, "expected \"Reservation Required\", but found {note:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseOwnerError {}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct HostInfo {
     pub hostname: String,
@@ -120,6 +518,23 @@ impl HostInfo {
 pub struct ClusterData {
     pub timestamp: u64,
     pub usage: ClusterUsage,
+    /// Information about the hive run that produced this data.
+    #[serde(default)]
+    pub run: Option<RunInfo>,
+}
+
+/// Information about a single run of the hive.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RunInfo {
+    /// Unix timestamp of the start of the run.
+    pub start: u64,
+    /// Duration of the run in seconds.
+    pub duration: f32,
+    /// Number of machines that the hive attempted to gather usage from.
+    pub attempted: u32,
+    /// Number of machines that the usage was gathered from successfully.
+    pub succeeded: u32,
+    pub hive_version: String,
 }
 
 impl ClusterData {
@@ -127,11 +542,7 @@ impl ClusterData {
     ///
     /// The timestamp will be generated from the current time.
     pub fn new(usage: ClusterUsage) -> Self {
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-            .unwrap() // Trust me, we exist after the unix epoch.
-            .as_secs();
-        Self { timestamp, usage }
+        Self { timestamp: unix_timestamp(), usage, run: None }
     }
 
     /// Returns the time stored in the timestamp of this [`ClusterData`].
@@ -140,6 +551,14 @@ impl ClusterData {
     }
 }
 
+/// Returns the current time as seconds since the unix epoch.
+pub fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap() // Trust me, we exist after the unix epoch.
+        .as_secs()
+}
+
 // Direct copy of `sysinfo::LoadAvg` to allow us to easily serialize this information.
 #[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct LoadAvg {
@@ -157,10 +576,175 @@ impl From<sysinfo::LoadAvg> for LoadAvg {
     }
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct Memory {
     pub total: u64,
     pub used: u64,
+    /// Memory that can be made available without swapping, including reclaimable caches, if known.
+    #[serde(default)]
+    pub available: Option<u64>,
+}
+
+impl Memory {
+    /// Returns the fraction of the memory that is in use and cannot simply be reclaimed.
+    pub fn fraction_used(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        let used = match self.available {
+            Some(available) => self.total.saturating_sub(available),
+            None => self.used,
+        };
+        used as f64 / self.total as f64
+    }
+}
+
+/// Pressure stall information, as reported by the kernel in `/proc/pressure`.
+///
+/// Each resource is [`None`] if the kernel does not provide pressure information for it.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Pressure {
+    pub cpu: Option<Stall>,
+    pub memory: Option<Stall>,
+    pub io: Option<Stall>,
+}
+
+/// Shares of time during which tasks were stalled on a resource.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct Stall {
+    /// Time during which at least some tasks were stalled.
+    pub some: StallAverages,
+    /// Time during which all non-idle tasks were stalled at once, if reported.
+    pub full: Option<StallAverages>,
+}
+
+/// Running averages of a share of time in percent, over the last 10, 60, and 300 seconds.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct StallAverages {
+    pub avg10: f32,
+    pub avg60: f32,
+    pub avg300: f32,
+}
+
+/// A login session, as recorded by systemd-logind, or by utmp on machines without it.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Session {
+    pub user: String,
+    /// The terminal or display of the session, such as `pts/0`, `tty2`, or `:0`.
+    pub line: String,
+    /// The host the session was opened from, if any. For sessions within a graphical session, this
+    /// is the display, such as `:0`.
+    pub host: Option<String>,
+    pub kind: SessionKind,
+    /// Unix timestamp of when the user logged in.
+    pub login_time: u64,
+    /// Seconds since the terminal or desktop of the session last saw any input, if known.
+    pub idle: Option<u64>,
+}
+
+impl Session {
+    /// Returns whether the user is sitting at the machine.
+    pub fn is_at_console(&self) -> bool {
+        matches!(self.kind, SessionKind::Graphical | SessionKind::Console)
+    }
+
+    /// Returns how long the user has been logged in, if the login time is known.
+    pub fn duration(&self) -> Option<std::time::Duration> {
+        runtime_since(Some(self.login_time).filter(|&login_time| login_time > 0))
+    }
+
+    /// Returns how long the terminal of the session had been idle when the bee looked, if known.
+    pub fn idle(&self) -> Option<std::time::Duration> {
+        self.idle.map(std::time::Duration::from_secs)
+    }
+}
+
+/// Where a [`Session`] takes place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum SessionKind {
+    /// A graphical session on a local display.
+    Graphical,
+    /// A text session on a local virtual console.
+    Console,
+    /// A session from another machine, typically over ssh.
+    Remote,
+    /// A terminal that is not tied to a place, such as one opened by tmux or screen.
+    Terminal,
+}
+
+impl std::fmt::Display for SessionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionKind::Graphical => write!(f, "seat"),
+            SessionKind::Console => write!(f, "tty"),
+            SessionKind::Remote => write!(f, "remote"),
+            SessionKind::Terminal => write!(f, "term"),
+        }
+    }
+}
+
+/// Temperatures and clock frequencies of the CPUs, which tell whether they are being throttled.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Thermal {
+    /// Temperature sensors of the CPU packages and cores.
+    pub sensors: Box<[Sensor]>,
+    /// Frequency of each logical CPU in MHz at the end of the sampling window, in the order of
+    /// [`Usage::cpus`].
+    pub frequencies: Box<[u64]>,
+    /// Highest frequency that the CPUs can run at in MHz, if known.
+    ///
+    /// This is the turbo frequency, which a few cores reach at a time, but all of them seldom do.
+    pub max_frequency: Option<u64>,
+    /// Frequency in MHz that the CPUs sustain with all cores busy, if the CPU driver reports it.
+    #[serde(default)]
+    pub base_frequency: Option<u64>,
+    /// Number of logical CPUs that were throttled because of their temperature during the sampling
+    /// window, if the kernel counts it.
+    #[serde(default)]
+    pub throttled_cpus: Option<u32>,
+}
+
+impl Thermal {
+    /// Returns the sensor with the highest temperature, if there are any.
+    pub fn hottest(&self) -> Option<&Sensor> {
+        self.sensors.iter().max_by(|a, b| a.temperature.total_cmp(&b.temperature))
+    }
+}
+
+/// A temperature sensor, such as `coretemp Package id 0`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Sensor {
+    pub label: String,
+    /// Temperature in degrees Celsius.
+    pub temperature: f32,
+    /// Temperature in degrees Celsius at which the hardware shuts down, if known.
+    pub critical: Option<f32>,
+}
+
+/// Throughput of a network interface, in bytes per second.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct NetworkIo {
+    pub interface: String,
+    pub received: u64,
+    pub transmitted: u64,
+}
+
+/// Throughput of a block device, such as `sda` or `nvme0n1`, in bytes per second.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DiskIo {
+    pub device: String,
+    pub read: u64,
+    pub written: u64,
+}
+
+/// Disk throughput of a process, in bytes per second.
+///
+/// This counts the bytes that the process caused to be read from or written to storage, so reads
+/// that were served from the page cache are left out.
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub struct ProcessIo {
+    pub read: u64,
+    pub written: u64,
 }
 
 // TODO: ?????????
@@ -170,7 +754,7 @@ pub struct Memory {
 // type UsageView = HashMap<String, Vec<Process>>;
 
 /// Per-process usage information for a single machine.
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct Processes(Box<[Process]>);
 
 impl std::ops::Deref for Processes {
@@ -199,19 +783,69 @@ impl Processes {
     }
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct Process {
     pub name: String,
     pub user: String,
+    /// Mean CPU usage over the sampling window, in percent of a single core.
     pub usage: f32,
+    /// Peak CPU usage over the sampling window, in percent of a single core, if known.
+    #[serde(default)]
+    pub peak_usage: Option<f32>,
+    #[serde(default)]
+    pub pid: u32,
+    /// Resident set size in bytes.
+    #[serde(default)]
+    pub rss: u64,
+    /// Virtual memory size in bytes.
+    #[serde(default)]
+    pub virtual_memory: u64,
+    /// Number of threads, if known.
+    #[serde(default)]
+    pub threads: Option<u32>,
+    /// Unix timestamp of when the process was started, if known.
+    #[serde(default)]
+    pub start_time: Option<u64>,
+    /// The command line, truncated and with anything that looks like a secret redacted.
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Number of processes combined into this entry, if it stands for many small processes of the
+    /// same user and name rather than a single process.
+    ///
+    /// Such an entry has no pid or command line, and its usage, memory, and threads are summed.
+    #[serde(default)]
+    pub combined: Option<u32>,
+    /// Mean disk throughput over the sampling window, if the bee could read it.
+    #[serde(default)]
+    pub io: Option<ProcessIo>,
 }
 
 impl Process {
     pub fn new(name: String, user: String, usage: f32) -> Self {
-        Self { name, user, usage }
+        Self { name, user, usage, ..Default::default() }
+    }
+
+    /// Returns the number of cores this entry takes up.
+    ///
+    /// A single process counts as one core, and combined processes as many as their summed usage.
+    pub fn cores(&self) -> u32 {
+        match self.combined {
+            None => 1,
+            Some(_) => ((self.usage / 100.0).round() as u32).max(1),
+        }
+    }
+
+    /// Returns how long the process has been running, if its start time is known.
+    pub fn runtime(&self) -> Option<std::time::Duration> {
+        runtime_since(self.start_time)
     }
 }
 
+fn runtime_since(start_time: Option<u64>) -> Option<std::time::Duration> {
+    let start_time = start_time?;
+    Some(std::time::Duration::from_secs(unix_timestamp().saturating_sub(start_time)))
+}
+
 pub struct CpuUsage {
     pub used: u32,
     pub total: u32,
@@ -220,5 +854,9 @@ pub struct CpuUsage {
 pub struct ActiveUser {
     pub user: String,
     pub cores: u32,
+    /// Number of busy GPUs on which the user is running processes.
+    pub gpus: u32,
     pub task: String,
+    /// How long the longest running process of the task has been running, if known.
+    pub runtime: Option<std::time::Duration>,
 }
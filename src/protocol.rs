@@ -0,0 +1,243 @@
+//! The wire protocol between `mu-bee` and `mu-hive`.
+//!
+//! A bee writes a single header line, followed by the serialized payload.
+//!
+//! ```text
+//! mu-bee {"protocol":1,"bee_version":"0.1.0","capabilities":["usage"]}
+//! { ... }
+//! ```
+//!
+//! The header states the protocol version of the payload, the version of the bee that wrote it, and
+//! the capabilities of that bee. Bees that predate this protocol write a bare [`Usage`] without any
+//! header. We treat that as protocol version 0.
+//!
+//! Since bees are rolled out to the machines gradually, the hive must be able to read payloads
+//! from older bees. Fields that are added to [`Usage`] must therefore have a sensible default.
+
+use std::io::Write;
+
+use crate::model::Usage;
+
+/// Marks the start of the header line.
+pub const MAGIC: &str = "mu-bee";
+/// The protocol version written by this build.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// The capabilities of a bee from this build.
+pub const CAPABILITIES: &[&str] = &[
+    "usage",
+    "mounts",
+    "mount-health",
+    "gpus",
+    "thresholds",
+    "jobs",
+    "sampling",
+    "pressure",
+    "io",
+    "inventory",
+    "thermal",
+    "sessions",
+];
+
+/// Describes the payload that follows it.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Header {
+    pub protocol: u32,
+    pub bee_version: String,
+    #[serde(default)]
+    pub capabilities: Box<[String]>,
+}
+
+impl Header {
+    /// Creates a [`Header`] describing this build.
+    pub fn new() -> Self {
+        Self {
+            protocol: PROTOCOL_VERSION,
+            bee_version: env!("CARGO_PKG_VERSION").to_string(),
+            capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    /// The implied header of a bee that does not write one.
+    fn legacy() -> Self {
+        Self { protocol: 0, bee_version: "?".to_string(), capabilities: Default::default() }
+    }
+
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+
+    /// Returns a description of the version mismatch with this build, if there is one.
+    pub fn mismatch(&self) -> Option<String> {
+        let Self { protocol, bee_version, .. } = self;
+        match (*protocol).cmp(&PROTOCOL_VERSION) {
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Less => Some(format!(
+                "bee {bee_version} speaks protocol {protocol}, older than {PROTOCOL_VERSION}"
+            )),
+            std::cmp::Ordering::Greater => Some(format!(
+                "bee {bee_version} speaks protocol {protocol}, newer than {PROTOCOL_VERSION}"
+            )),
+        }
+    }
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A decoded message from a bee.
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub header: Header,
+    pub usage: Usage,
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Header(serde_json::Error),
+    Payload { protocol: u32, error: serde_json::Error },
+    Unsupported { protocol: u32, bee_version: String, error: serde_json::Error },
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Header(error) => write!(f, "could not read header: {error}"),
+            DecodeError::Payload { protocol, error } => {
+                write!(f, "could not read payload for protocol {protocol}: {error}")
+            }
+            DecodeError::Unsupported { protocol, bee_version, error } => write!(
+                f,
+                "bee {bee_version} speaks unsupported protocol {protocol} \
+                (expected at most {PROTOCOL_VERSION}): {error}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Writes the header and the `usage` payload.
+pub fn write(mut writer: impl Write, usage: &Usage) -> std::io::Result<()> {
+    write!(writer, "{MAGIC} ")?;
+    serde_json::to_writer(&mut writer, &Header::new())?;
+    writeln!(writer)?;
+    serde_json::to_writer(&mut writer, usage)?;
+    writer.flush()
+}
+
+/// Reads a message written by a bee of any protocol version.
+pub fn read(bytes: &[u8]) -> Result<Message, DecodeError> {
+    let (header, payload) = match bytes.strip_prefix(MAGIC.as_bytes()) {
+        Some(rest) => {
+            let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            let (header, payload) = rest.split_at(end);
+            let header = serde_json::from_slice(header).map_err(DecodeError::Header)?;
+            (header, payload)
+        }
+        None => (Header::legacy(), bytes),
+    };
+
+    let usage = match header.protocol {
+        // Versions 0 and 1 share the same payload layout.
+        0..=PROTOCOL_VERSION => serde_json::from_slice(payload)
+            .map_err(|error| DecodeError::Payload { protocol: header.protocol, error })?,
+        // A newer bee may still produce a payload we understand, since unknown fields are
+        // ignored. Only if that fails do we give up on it.
+        protocol => serde_json::from_slice(payload).map_err(|error| DecodeError::Unsupported {
+            protocol,
+            bee_version: header.bee_version.clone(),
+            error,
+        })?,
+    };
+
+    Ok(Message { header, usage })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A payload as written by a bee from before this protocol, without any of the later fields.
+    const LEGACY: &str = r#"{
+        "global_cpu_usage": 12.5,
+        "cpus": [25.0, 0.0],
+        "load_avg": {"one": 0.5, "five": 0.25, "fifteen": 0.125},
+        "mem": {"total": 8192, "used": 4096},
+        "processes": [{"name": "python3", "user": "marieke", "usage": 25.0}]
+    }"#;
+
+    fn usage() -> Usage {
+        serde_json::from_str(LEGACY).unwrap()
+    }
+
+    #[test]
+    fn round_trips_the_header_and_payload() {
+        let mut bytes = Vec::new();
+        write(&mut bytes, &usage()).unwrap();
+        assert!(bytes.starts_with(b"mu-bee {"));
+        assert_eq!(bytes.iter().filter(|&&b| b == b'\n').count(), 1);
+
+        let Message { header, usage } = read(&bytes).unwrap();
+        assert_eq!(header, Header::new());
+        assert_eq!(header.mismatch(), None);
+        assert!(header.has_capability("usage"));
+        assert!(!header.has_capability("teleportation"));
+        assert_eq!(usage.global_cpu_usage, 12.5);
+        assert_eq!(usage.processes[0].name, "python3");
+    }
+
+    #[test]
+    fn reads_a_legacy_payload_without_a_header() {
+        let Message { header, usage } = read(LEGACY.as_bytes()).unwrap();
+        assert_eq!(header.protocol, 0);
+        assert!(header.capabilities.is_empty());
+        assert!(header.mismatch().unwrap().contains("older than"));
+        assert_eq!(&*usage.cpus, [25.0, 0.0]);
+        assert_eq!(usage.mem.available, None);
+        assert!(usage.mounts.is_empty());
+        assert_eq!(usage.processes[0].pid, 0);
+    }
+
+    #[test]
+    fn reads_a_payload_from_a_newer_bee() {
+        let protocol = PROTOCOL_VERSION + 1;
+        let newer = format!(
+            "mu-bee {{\"protocol\":{protocol},\"bee_version\":\"9.0.0\",\"capabilities\":[\"usage\"]}}\n\
+             {}",
+            LEGACY.replacen('{', "{\"quantum_flux\": 3,", 1)
+        );
+        let Message { header, usage } = read(newer.as_bytes()).unwrap();
+        assert_eq!(header.protocol, protocol);
+        assert!(header.mismatch().unwrap().contains("newer than"));
+        assert_eq!(usage.global_cpu_usage, 12.5);
+
+        // A newer payload that no longer fits is reported as such.
+        let incompatible = newer.replace("\"global_cpu_usage\": 12.5", "\"global_cpu_usage\": []");
+        let error = read(incompatible.as_bytes()).unwrap_err();
+        assert!(
+            matches!(&error, DecodeError::Unsupported { protocol: p, .. } if *p == protocol),
+            "{error:?}"
+        );
+        assert!(error.to_string().contains("bee 9.0.0 speaks unsupported protocol"), "{error}");
+    }
+
+    #[test]
+    fn reports_a_malformed_header_or_payload() {
+        let error = read(format!("mu-bee {{\"protocol\":\n{LEGACY}").as_bytes()).unwrap_err();
+        assert!(matches!(error, DecodeError::Header(_)), "{error:?}");
+        let error = read(format!("mu-bee not json\n{LEGACY}").as_bytes()).unwrap_err();
+        assert!(matches!(error, DecodeError::Header(_)), "{error:?}");
+        // A header without capabilities is fine, since they were added later.
+        let header = "mu-bee {\"protocol\":1,\"bee_version\":\"0.1.0\"}";
+        let message = read(format!("{header}\n{LEGACY}").as_bytes()).unwrap();
+        assert!(message.header.capabilities.is_empty());
+
+        let error = read(format!("{header}\n{{\"cpus\": 3}}").as_bytes()).unwrap_err();
+        assert!(matches!(error, DecodeError::Payload { protocol: 1, .. }), "{error:?}");
+        let error = read(b"not a bee").unwrap_err();
+        assert!(matches!(error, DecodeError::Payload { protocol: 0, .. }), "{error:?}");
+    }
+}
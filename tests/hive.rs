@@ -0,0 +1,210 @@
+//! Runs the hive against fake machines, whose bees are stub scripts on this machine.
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use mu::model::{ClusterData, MachineUsage, Status};
+
+/// Creates an empty directory for a single test.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mu-hive-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes an executable shell script with the given body.
+fn script(dir: &Path, name: &str, body: &str) -> String {
+    let path = dir.join(name);
+    std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+/// Runs the hive once over the `machines` file, and returns the data it wrote to `output`.
+fn run_hive(machines: &Path, output: &Path, args: &[&str]) -> ClusterData {
+    let hive = Command::new(env!("CARGO_BIN_EXE_mu-hive"))
+        .arg("--machines")
+        .arg(machines)
+        .arg("--output")
+        .arg(output)
+        .args(["--bee", env!("CARGO_BIN_EXE_mu-bee")])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(hive.status.success(), "{}", String::from_utf8_lossy(&hive.stderr));
+    serde_json::from_slice(&std::fs::read(output).unwrap()).unwrap()
+}
+
+fn machine<'a>(data: &'a ClusterData, hostname: &str) -> &'a MachineUsage {
+    let machine = data.usage.iter().find(|machine| machine.definition.hostname == hostname);
+    machine.unwrap_or_else(|| panic!("{hostname} is missing from the output"))
+}
+
+fn status_of(data: &ClusterData, hostname: &str) -> Status {
+    machine(data, hostname).status
+}
+
+#[test]
+fn records_the_status_of_each_machine() {
+    let dir = scratch_dir("status");
+    let bee = env!("CARGO_BIN_EXE_mu-bee");
+    let ok = script(&dir, "ok", &format!("exec {bee} \"$@\""));
+    let exit = script(&dir, "exit", "exit 3");
+    let garbage = script(&dir, "garbage", "echo 'not a bee'");
+    let hang = script(&dir, "hang", "exec sleep 30");
+    // The template stub only succeeds if the placeholders were filled in.
+    let templated = script(
+        &dir,
+        "templated",
+        &format!("[ \"$1\" = mu ] && [ \"$2\" = 2222 ] || exit 9\nshift 2\nexec {bee} \"$@\""),
+    );
+    let machines = dir.join("machines.ini");
+    std::fs::write(
+        &machines,
+        format!(
+            "[lab]\n\
+             ok: | transport=local bee={ok}\n\
+             exit: | transport=local bee={exit}\n\
+             garbage: | transport=local bee={garbage}\n\
+             hang: | transport=local bee={hang}\n\
+             templated: | transport=command command=\"{templated} {{user}} {{port}} {{bee}}\" \
+             user=mu port=2222\n"
+        ),
+    )
+    .unwrap();
+    let output = dir.join("mu.dat");
+
+    let args = ["--retries", "0", "--command-timeout", "2", "--deadline", "30"];
+    let data = run_hive(&machines, &output, &args);
+    assert_eq!(status_of(&data, "ok"), Status::Ok);
+    assert_eq!(status_of(&data, "exit"), Status::BeeExit(Some(3)));
+    assert_eq!(status_of(&data, "garbage"), Status::Deserialization);
+    assert_eq!(status_of(&data, "hang"), Status::Timeout);
+    assert_eq!(status_of(&data, "templated"), Status::Ok);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn asks_for_a_window_only_from_bees_that_can_sample() {
+    let dir = scratch_dir("sampling");
+    let bee = env!("CARGO_BIN_EXE_mu-bee");
+    let args = dir.join("args");
+    let sampling = script(&dir, "sampling", &format!("echo \"$@\" >> {args:?}\nexec {bee} \"$@\""));
+    // A bee from before sampling takes the first argument for its config file.
+    let old = script(
+        &dir,
+        "old",
+        &format!(
+            "[ $# -le 1 ] || exit 9\n\
+             {bee} \"$@\" | sed 's/\"sampling\",//'"
+        ),
+    );
+    let machines = dir.join("machines.ini");
+    std::fs::write(
+        &machines,
+        format!(
+            "[lab]\n\
+             sampling: | transport=local bee={sampling} window=0.5 samples=2\n\
+             old: | transport=local bee={old} window=0.5 samples=2\n"
+        ),
+    )
+    .unwrap();
+    let output = dir.join("mu.dat");
+
+    // Nothing is known about the bees in the first run, so neither is asked for a window.
+    let data = run_hive(&machines, &output, &["--retries", "0"]);
+    assert_eq!(status_of(&data, "sampling"), Status::Ok);
+    assert_eq!(status_of(&data, "old"), Status::Ok);
+    let header = machine(&data, "old").bee.as_ref().unwrap();
+    assert!(!header.has_capability("sampling"));
+
+    let data = run_hive(&machines, &output, &["--retries", "0"]);
+    assert_eq!(status_of(&data, "sampling"), Status::Ok);
+    assert_eq!(status_of(&data, "old"), Status::Ok);
+    let args = std::fs::read_to_string(&args).unwrap();
+    let runs = args.lines().collect::<Vec<_>>();
+    assert_eq!(runs.len(), 2);
+    assert!(!runs[0].contains("--window"), "{args}");
+    assert!(runs[1].contains("--window 0.5 --samples 2"), "{args}");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn check_reports_unknown_placeholders() {
+    let dir = scratch_dir("check");
+    let machines = dir.join("machines.ini");
+    std::fs::write(
+        &machines,
+        "[lab]\n\
+         good: | transport=command command=\"ssh -p {port} {user}@{host} {bee}\" user=mu port=22\n\
+         typo: | transport=command command=\"ssh {hots} {bee}\"\n\
+         unset: | transport=command command=\"ssh {user}@{host} {bee}\"\n",
+    )
+    .unwrap();
+
+    let check = Command::new(env!("CARGO_BIN_EXE_mu-hive"))
+        .arg("check")
+        .arg("--machines")
+        .arg(&machines)
+        .output()
+        .unwrap();
+    assert!(!check.status.success());
+    let report = String::from_utf8_lossy(&check.stdout);
+    assert!(!report.contains(":2:"), "{report}");
+    assert!(report.contains(":3: unknown placeholder {hots}"), "{report}");
+    assert!(report.contains(":4: the command template uses {user}"), "{report}");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn skips_a_run_while_another_holds_the_lock() {
+    let dir = scratch_dir("lock");
+    let bee = env!("CARGO_BIN_EXE_mu-bee");
+    let ok = script(&dir, "ok", &format!("exec {bee} \"$@\""));
+    let machines = dir.join("machines.ini");
+    std::fs::write(&machines, format!("[lab]\nok: | transport=local bee={ok}\n")).unwrap();
+    let output = dir.join("mu.dat");
+
+    let data = run_hive(&machines, &output, &[]);
+    let run = data.run.unwrap();
+    assert_eq!((run.attempted, run.succeeded), (1, 1));
+    assert_eq!(run.hive_version, env!("CARGO_PKG_VERSION"));
+    assert!(run.start <= data.timestamp);
+    let written = std::fs::read(&output).unwrap();
+
+    let lock = std::fs::File::create(dir.join("mu.dat.lock")).unwrap();
+    lock.try_lock().unwrap();
+    let start = std::time::Instant::now();
+    let hive = Command::new(env!("CARGO_BIN_EXE_mu-hive"))
+        .arg("--machines")
+        .arg(&machines)
+        .arg("--output")
+        .arg(&output)
+        .args(["--bee", bee])
+        .output()
+        .unwrap();
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    assert!(hive.status.success(), "{}", String::from_utf8_lossy(&hive.stderr));
+    assert!(String::from_utf8_lossy(&hive.stderr).contains("Skipping this run"));
+    assert_eq!(std::fs::read(&output).unwrap(), written);
+
+    // A daemon cannot start at all.
+    let daemon = Command::new(env!("CARGO_BIN_EXE_mu-hive"))
+        .arg("daemon")
+        .arg("--machines")
+        .arg(&machines)
+        .arg("--output")
+        .arg(&output)
+        .args(["--bee", bee])
+        .output()
+        .unwrap();
+    assert!(!daemon.status.success());
+    assert!(String::from_utf8_lossy(&daemon.stderr).contains("another run is holding"));
+
+    drop(lock);
+    let data = run_hive(&machines, &output, &[]);
+    assert_eq!(status_of(&data, "ok"), Status::Ok);
+    let _ = std::fs::remove_dir_all(&dir);
+}